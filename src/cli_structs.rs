@@ -1,12 +1,50 @@
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
+use clap::ValueEnum;
+
+use crate::reg_gen::bitfield_import_handling::BitOrder;
+use crate::reg_gen::format_handling::ConfigFormat;
+use crate::reg_gen::spreadsheet_handling::Column;
+use crate::reg_gen::validation::SUPPORTED_REGISTER_WIDTHS;
 
 // CLI parsing structs
 #[derive(Subcommand)]
 pub enum Commands {
     /// Add a register to an existing JSON configuration file
     AddRegister(AddRegisterArgs),
+    /// Add a field to an existing register without any prompts
+    AddField(AddFieldArgs),
+    /// Remove a register from a JSON configuration file
+    RemoveRegister(RemoveRegisterArgs),
+    /// Remove a field from a register
+    RemoveField(RemoveFieldArgs),
+    /// Change attributes of a single register or field
+    Edit(EditArgs),
+    /// Split a raw register value into its fields
+    Decode(DecodeArgs),
+    /// Build a raw register value from field values
+    Encode(EncodeArgs),
+    /// Decode the register writes in a log into field changes
+    Trace(TraceArgs),
+    /// Compare two versions of a configuration and classify the changes
+    Diff(DiffArgs),
+    /// Combine several configurations into one
+    Merge(MergeArgs),
+    /// Move registers out of a configuration into a new one
+    Split(SplitArgs),
+    /// Rewrite configurations in canonical form
+    Fmt(FmtArgs),
+    /// Print the JSON Schema of the configuration format
+    Schema,
+    /// Translate a configuration between JSON, YAML and TOML
+    Convert(ConvertArgs),
+    /// Create or update a configuration from a CSV or XLSX register map
+    Import(ImportArgs),
+    /// Write a configuration out as a CSV or XLSX register map
+    Export(ExportArgs),
+    /// Create or update a configuration from the register macros and bitfield structs in C headers
+    ImportC(ImportCArgs),
     /// Generate new header files
     Generate(GenerateArgs),
     /// Creates an initial JSON file for a new register family
@@ -21,6 +59,14 @@ pub enum Commands {
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Format of every configuration file, instead of detecting it from the file extension
+    #[arg(long, global = true, value_enum)]
+    pub format: Option<ConfigFormat>,
+
+    /// Rewrite YAML and TOML configurations even though their comments will be lost
+    #[arg(long, global = true)]
+    pub discard_comments: bool,
 }
 
 #[derive(Args)]
@@ -34,6 +80,296 @@ pub struct AddRegisterArgs {
     /// Size of the new register (must be supported by register family)
     #[arg(short, long, value_parser = register_size_supported)]
     pub size: u8,
+    /// Add the register without prompting for fields, they can be added later with add-field
+    #[arg(long)]
+    pub no_fields: bool,
+    /// Byte offset of the register from the family's base address
+    #[arg(long, value_parser = parse_register_value)]
+    pub offset: Option<u64>,
+    /// One line description, emitted as a Doxygen comment in the generated header
+    #[arg(long)]
+    pub description: Option<String>,
+    /// Longer description, emitted below the one line description
+    #[arg(long)]
+    pub long_description: Option<String>,
+}
+
+#[derive(Args)]
+pub struct AddFieldArgs {
+    /// Path to the configuration JSON
+    #[arg(short, long)]
+    pub path: String,
+    /// Name of the register to add the field to
+    #[arg(short, long)]
+    pub register: String,
+    /// Name of the new field
+    #[arg(short, long)]
+    pub name: String,
+    /// Least significant bit of the field
+    #[arg(long)]
+    pub lsb: u8,
+    /// Most significant bit of the field
+    #[arg(long)]
+    pub msb: u8,
+    /// Generate a get method for the field
+    #[arg(long)]
+    pub read: bool,
+    /// Generate a set method for the field
+    #[arg(long)]
+    pub write: bool,
+    /// Treat the field as a two's complement signed number
+    #[arg(long)]
+    pub negative: bool,
+    /// Raw value of the field after reset
+    #[arg(long, value_parser = parse_register_value)]
+    pub reset: Option<u64>,
+    /// One line description, emitted as a Doxygen comment in the generated header
+    #[arg(long)]
+    pub description: Option<String>,
+    /// Longer description, emitted below the one line description
+    #[arg(long)]
+    pub long_description: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RemoveRegisterArgs {
+    /// Path to the configuration JSON
+    #[arg(short, long)]
+    pub path: String,
+    /// Name of the register to remove
+    #[arg(short, long)]
+    pub name: String,
+}
+
+#[derive(Args)]
+pub struct RemoveFieldArgs {
+    /// Path to the configuration JSON
+    #[arg(short, long)]
+    pub path: String,
+    /// Name of the register containing the field
+    #[arg(short, long)]
+    pub register: String,
+    /// Name of the field to remove
+    #[arg(short, long)]
+    pub name: String,
+}
+
+#[derive(Args)]
+pub struct EditArgs {
+    /// Path to the configuration JSON
+    #[arg(short, long)]
+    pub path: String,
+    /// Name of the register to edit, or containing the field to edit
+    #[arg(short, long)]
+    pub register: String,
+    /// Name of the field to edit, leave out to edit the register itself
+    #[arg(short, long)]
+    pub field: Option<String>,
+    /// New name for the register or field
+    #[arg(long)]
+    pub name: Option<String>,
+    /// New size of the register
+    #[arg(long, value_parser = register_size_supported)]
+    pub size: Option<u8>,
+    /// New byte offset of the register from the family's base address
+    #[arg(long, value_parser = parse_register_value)]
+    pub offset: Option<u64>,
+    /// New least significant bit of the field
+    #[arg(long)]
+    pub lsb: Option<u8>,
+    /// New most significant bit of the field
+    #[arg(long)]
+    pub msb: Option<u8>,
+    /// Whether the field has a get method
+    #[arg(long)]
+    pub read: Option<bool>,
+    /// Whether the field has a set method
+    #[arg(long)]
+    pub write: Option<bool>,
+    /// Whether the field is signed
+    #[arg(long)]
+    pub negative: Option<bool>,
+    /// New raw value of the field after reset
+    #[arg(long, value_parser = parse_register_value)]
+    pub reset: Option<u64>,
+    /// New one line description, or "" to remove it
+    #[arg(long)]
+    pub description: Option<String>,
+    /// New longer description, or "" to remove it
+    #[arg(long)]
+    pub long_description: Option<String>,
+}
+
+#[derive(Args)]
+pub struct DecodeArgs {
+    /// Path to the configuration JSON
+    #[arg(short, long)]
+    pub path: String,
+    /// Name of the register the value was read from
+    #[arg(short, long)]
+    pub register: String,
+    /// Raw register value, in decimal, hex (0x) or binary (0b)
+    #[arg(value_parser = parse_register_value)]
+    pub value: u64,
+}
+
+#[derive(Args)]
+pub struct EncodeArgs {
+    /// Path to the configuration JSON
+    #[arg(short, long)]
+    pub path: String,
+    /// Name of the register to build a value for
+    #[arg(short, long)]
+    pub register: String,
+    /// Raw register value to start from instead of zero
+    #[arg(long, value_parser = parse_register_value, default_value = "0")]
+    pub initial: u64,
+    /// Field values as field=value, where value is a number or one of the field's enum names
+    #[arg(required = true)]
+    pub fields: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct TraceArgs {
+    /// Path to a configuration file, or directory of them, whose registers the log writes to (repeatable)
+    #[arg(short, long, required = true)]
+    pub path: Vec<String>,
+    /// Regex matching a register write, with named groups `address` and `value`
+    #[arg(long, default_value = DEFAULT_TRACE_PATTERN)]
+    pub pattern: String,
+    /// Log file to decode
+    pub log: String,
+}
+
+pub const DEFAULT_TRACE_PATTERN: &str = r"W\s+(?P<address>0[xX][0-9a-fA-F_]+)\s*=\s*(?P<value>0[xX][0-9a-fA-F_]+)";
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Path to the old configuration JSON
+    pub old: String,
+    /// Path to the new configuration JSON
+    pub new: String,
+    /// Print the changes as a table or as JSON
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output_format: OutputFormat,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args)]
+pub struct MergeArgs {
+    /// Paths to the configuration JSONs to merge, the first one names the merged family
+    #[arg(num_args = 2.., required = true)]
+    pub paths: Vec<String>,
+    /// Path to the merged configuration JSON
+    #[arg(short, long)]
+    pub output: String,
+    /// Name of the merged register family, instead of the first family's name
+    #[arg(short, long)]
+    pub name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct SplitArgs {
+    /// Path to the configuration JSON to move registers out of
+    #[arg(short, long)]
+    pub path: String,
+    /// Names of the registers to move
+    #[arg(short, long, num_args = 1.., required = true)]
+    pub registers: Vec<String>,
+    /// Path to the new configuration JSON, which must not exist yet
+    #[arg(short, long)]
+    pub output: String,
+    /// Name of the new register family
+    #[arg(short, long)]
+    pub name: String,
+}
+
+#[derive(Args)]
+pub struct FmtArgs {
+    /// Path to a configuration file, or directory of them, to format (repeatable)
+    #[arg(short, long, required = true)]
+    pub path: Vec<String>,
+    /// Only report files that aren't formatted, failing if there are any
+    #[arg(long)]
+    pub check: bool,
+}
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// Path to the configuration to convert
+    #[arg(short, long)]
+    pub path: String,
+    /// Format to convert from, instead of detecting it from the input file extension
+    #[arg(long, value_enum)]
+    pub from: Option<ConfigFormat>,
+    /// Path to write the converted configuration to
+    #[arg(short, long)]
+    pub output: String,
+    /// Format to convert to, instead of detecting it from the output file extension
+    #[arg(long, value_enum)]
+    pub to: Option<ConfigFormat>,
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// Path to the CSV or XLSX spreadsheet
+    #[arg(short, long)]
+    pub path: String,
+    /// Path to the configuration to write, an existing one keeps its family attributes and has its registers replaced
+    #[arg(short, long)]
+    pub output: String,
+    /// Name of the register family, instead of the existing one or the spreadsheet's file name
+    #[arg(short, long)]
+    pub name: Option<String>,
+    /// Worksheet of an XLSX file to read, instead of the first one
+    #[arg(long)]
+    pub sheet: Option<String>,
+    /// Header of a column in the spreadsheet, such as `lsb="Start Bit"` (repeatable)
+    #[arg(short, long, value_parser = parse_column_mapping)]
+    pub column: Vec<(Column, String)>,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Path to the configuration to export
+    #[arg(short, long)]
+    pub path: String,
+    /// Path to the CSV or XLSX spreadsheet to write
+    #[arg(short, long)]
+    pub output: String,
+    /// Header to write for a column, such as `lsb="Start Bit"` (repeatable)
+    #[arg(short, long, value_parser = parse_column_mapping)]
+    pub column: Vec<(Column, String)>,
+}
+
+#[derive(Args)]
+pub struct ImportCArgs {
+    /// Path to a C header to read (repeatable)
+    #[arg(short, long, required = true)]
+    pub path: Vec<String>,
+    /// Path to the configuration to write, an existing one keeps its family attributes and has its registers replaced
+    #[arg(short, long)]
+    pub output: String,
+    /// Name of the register family, instead of the existing one or the first header's file name
+    #[arg(short, long)]
+    pub name: Option<String>,
+    /// Size of registers, unless their fields need a wider one
+    #[arg(short, long, value_parser = register_size_supported, default_value_t = 32)]
+    pub size: u8,
+    /// Prefix to remove from every macro name, such as `MYCHIP_`
+    #[arg(long)]
+    pub prefix: Option<String>,
+    /// End of the register the first field of a bitfield struct is placed at, as the compiler's ABI does
+    #[arg(long, value_enum, default_value_t = BitOrder::LsbFirst)]
+    pub bit_order: BitOrder,
+    /// Path to write the macros that need reviewing to, instead of printing them
+    #[arg(short, long)]
+    pub report: Option<String>,
 }
 
 #[derive(Args)]
@@ -41,6 +377,32 @@ pub struct GenerateArgs {
     /// Path to the configuration JSON
     #[arg(short, long)]
     pub path: String,
+    /// What to generate from the configuration
+    #[arg(short, long, value_enum, default_value_t = Backend::Cpp)]
+    pub backend: Backend,
+    /// Also generate volatile read_from, write_to and modify methods, and a peripheral struct for families with a base address (cpp backend only)
+    #[arg(long)]
+    pub mmio: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Backend {
+    /// C++ register classes in {family}Registers.h and Register{width}.h
+    Cpp,
+    /// A searchable register manual in {family}.html
+    Html,
+    /// A register reference in {family}.md
+    Markdown,
+    /// A register reference in {family}.adoc
+    Asciidoc,
+    /// A bitfield diagram of each register in {family}_{register}.svg
+    Svg,
+    /// A WaveDrom bitfield description of each register in {family}_{register}.json5
+    Wavedrom,
+    /// Linux kernel GENMASK() and BIT() macros in {family}_regs.h
+    Linux,
+    /// A Python class per register in {family}_registers.py
+    Python,
 }
 
 #[derive(Args)]
@@ -52,23 +414,71 @@ pub struct BootstrapArgs {
     /// Name of the register family
     #[arg(short, long)]
     pub name: String,
+
+    /// Address that register offsets are relative to
+    #[arg(long, value_parser = parse_register_value)]
+    pub base_address: Option<u64>,
+
+    /// One line description, emitted as a Doxygen comment in the generated header
+    #[arg(long)]
+    pub description: Option<String>,
+
+    /// Longer description, emitted below the one line description
+    #[arg(long)]
+    pub long_description: Option<String>,
 }
 
 #[derive(Args)]
 pub struct TuiArgs {
-    /// Path to the JSON file
-    #[arg(short, long)]
-    pub path: String,
+    /// Paths to configuration files, or directories of them, each opened as its own family
+    #[arg(short, long, num_args = 1.., required = true)]
+    pub path: Vec<String>,
+
+    /// Built-in keymap and theme preset (default, vim or high-contrast), overriding the config file
+    #[arg(long)]
+    pub preset: Option<String>,
 }
 
 fn register_size_supported(size: &str) -> Result<u8, String> {
-    let supported_sizes = vec![8, 16, 32, 64];
-    let size: usize = size
+    let size: u8 = size
         .parse()
         .map_err(|_| format!("`{size}` isn't a number"))?;
-    if supported_sizes.contains(&size) {
-        Ok(size as u8)
+    if SUPPORTED_REGISTER_WIDTHS.contains(&size) {
+        Ok(size)
     } else {
         Err("Unsupported size. Supported register sizes are: 8, 16, 32, 64".to_string())
     }
 }
+
+fn parse_register_value(value: &str) -> Result<u64, String> {
+    let number = parse_number(value)?;
+    u64::try_from(number).map_err(|_| format!("`{value}` doesn't fit in a 64-bit register"))
+}
+
+// Parses column mappings such as `long_description=Details`
+fn parse_column_mapping(value: &str) -> Result<(Column, String), String> {
+    let (column, header) = value
+        .split_once('=')
+        .ok_or(format!("`{value}` should look like column=header"))?;
+    let column = Column::from_str(&column.trim().replace('_', "-"), true)?;
+    Ok((column, header.trim().to_string()))
+}
+
+// Accepts decimal, 0x hex and 0b binary, optionally negative and with _ separators
+pub fn parse_number(value: &str) -> Result<i128, String> {
+    let cleaned = value.trim().replace('_', "");
+    let (negative, digits) = match cleaned.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, cleaned.as_str()),
+    };
+    let lower = digits.to_ascii_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        i128::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i128::from_str_radix(binary, 2)
+    } else {
+        lower.parse()
+    };
+    let number = parsed.map_err(|_| format!("`{value}` isn't a number"))?;
+    Ok(if negative { -number } else { number })
+}
@@ -1,4 +1,4 @@
-use std::fs::OpenOptions;
+use std::fs;
 use std::io;
 use std::io::Write;
 
@@ -6,7 +6,20 @@ use crate::cli_structs::*;
 use crate::reg_gen::register::*;
 use crate::reg_gen::json_handling::*;
 use crate::reg_gen::header_handling::*;
+use crate::reg_gen::html_handling::generate_html;
+use crate::reg_gen::reference_handling::*;
+use crate::reg_gen::diagram_handling::*;
+use crate::reg_gen::linux_handling::generate_linux_header;
+use crate::reg_gen::python_handling::generate_python;
+use crate::reg_gen::diff::*;
+use crate::reg_gen::family_handling::*;
+use crate::reg_gen::format_handling::{discard_comments, format_override, ConfigFormat};
+use crate::reg_gen::spreadsheet_handling::*;
+use crate::reg_gen::c_import_handling::*;
+use crate::reg_gen::text_table::format_table;
+use crate::reg_gen::trace_handling::trace_log;
 use crate::tui_handler::*;
+use crate::tui_config::TuiConfig;
 
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
@@ -20,89 +33,468 @@ use tui::{
 };
 
 pub fn add_register_handler(args: AddRegisterArgs) -> Result<(), std::io::Error> {
-    let mut register_family = pull_existing_json(&args.path);
-
-    if !register_family.register_family_widths.contains(&args.size) {
-        register_family.register_family_widths.push(args.size);
-    }
+    let mut register_family = pull_existing_json(&args.path)?;
 
     let mut register = Register {
         name: args.name,
         size: args.size,
-        fields: vec![],
+        offset: args.offset,
+        description: args.description,
+        long_description: args.long_description,
+        ..Default::default()
     };
 
-    loop {
-        print!("Would you like to add a(nother) field to this register (y/n): ");
-        io::stdout().flush().unwrap();
-        let mut response = String::new();
+    if !args.no_fields {
+        loop {
+            print!("Would you like to add a(nother) field to this register (y/n): ");
+            io::stdout().flush().unwrap();
+            let mut response = String::new();
 
-        io::stdin().read_line(&mut response).expect("Failed to read line!");
-        response = response.trim().to_string();
+            io::stdin().read_line(&mut response).expect("Failed to read line!");
+            response = response.trim().to_string();
 
-        match response.as_str() {
-            "y" => {
-                register.add_register_field();
-            },
-            "n" => {
-                break;
-            },
-            _ => {
-                println!("Invalid input!");
-                continue;
+            match response.as_str() {
+                "y" => {
+                    register.add_register_field();
+                },
+                "n" => {
+                    break;
+                },
+                _ => {
+                    println!("Invalid input!");
+                    continue;
+                }
             }
         }
     }
 
     register_family.registers.push(register);
 
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(false)
-        .truncate(true)
-        .open(args.path.clone())
-        .expect("Could not open JSON file!");
+    write_register_family(&args.path, &register_family)
+}
+
+pub fn add_field_handler(args: AddFieldArgs) -> Result<(), std::io::Error> {
+    let mut register_family = pull_existing_json(&args.path)?;
+
+    let register = find_register(&mut register_family, &args.register)?;
+    register.fields.push(Field {
+        name: args.name,
+        lsb: args.lsb,
+        msb: args.msb,
+        read: args.read,
+        write: args.write,
+        negative: Some(args.negative),
+        reset: args.reset,
+        description: args.description,
+        long_description: args.long_description,
+        ..Default::default()
+    });
+
+    write_register_family(&args.path, &register_family)
+}
+
+pub fn remove_register_handler(args: RemoveRegisterArgs) -> Result<(), std::io::Error> {
+    let mut register_family = pull_existing_json(&args.path)?;
+
+    let index = register_family.registers
+        .iter()
+        .position(|register| register.name == args.name)
+        .ok_or(not_found(format!("No register named `{}`", args.name)))?;
+    register_family.registers.remove(index);
+
+    write_register_family(&args.path, &register_family)
+}
+
+pub fn remove_field_handler(args: RemoveFieldArgs) -> Result<(), std::io::Error> {
+    let mut register_family = pull_existing_json(&args.path)?;
+
+    let register = find_register(&mut register_family, &args.register)?;
+    let index = register.fields
+        .iter()
+        .position(|field| field.name == args.name)
+        .ok_or(not_found(format!("No field named `{}` in register `{}`", args.name, args.register)))?;
+    register.fields.remove(index);
+
+    write_register_family(&args.path, &register_family)
+}
+
+pub fn edit_handler(args: EditArgs) -> Result<(), std::io::Error> {
+    let mut register_family = pull_existing_json(&args.path)?;
+
+    let field_only = args.lsb.is_some() || args.msb.is_some() || args.read.is_some() || args.write.is_some() || args.negative.is_some() || args.reset.is_some();
+    let register = find_register(&mut register_family, &args.register)?;
+
+    match &args.field {
+        Some(field_name) => {
+            if args.size.is_some() || args.offset.is_some() {
+                return Err(invalid_input(String::from("--size and --offset apply to registers, leave out --field to change them")));
+            }
+            let field = register.fields
+                .iter_mut()
+                .find(|field| &field.name == field_name)
+                .ok_or(not_found(format!("No field named `{}` in register `{}`", field_name, args.register)))?;
+
+            if let Some(name) = args.name { field.name = name; }
+            if let Some(lsb) = args.lsb { field.lsb = lsb; }
+            if let Some(msb) = args.msb { field.msb = msb; }
+            if let Some(read) = args.read { field.read = read; }
+            if let Some(write) = args.write { field.write = write; }
+            if let Some(negative) = args.negative { field.negative = Some(negative); }
+            if let Some(reset) = args.reset { field.reset = Some(reset); }
+            if let Some(description) = args.description { field.description = non_empty(description); }
+            if let Some(long_description) = args.long_description { field.long_description = non_empty(long_description); }
+        },
+        None => {
+            if field_only {
+                return Err(invalid_input(String::from("--lsb, --msb, --read, --write, --negative and --reset apply to fields, pass --field to choose one")));
+            }
+            if let Some(name) = args.name { register.name = name; }
+            if let Some(size) = args.size { register.size = size; }
+            if let Some(offset) = args.offset { register.offset = Some(offset); }
+            if let Some(description) = args.description { register.description = non_empty(description); }
+            if let Some(long_description) = args.long_description { register.long_description = non_empty(long_description); }
+        },
+    }
+
+    write_register_family(&args.path, &register_family)
+}
+
+fn find_register<'a>(register_family: &'a mut RegisterFamily, name: &String) -> Result<&'a mut Register, std::io::Error> {
+    register_family.registers
+        .iter_mut()
+        .find(|register| &register.name == name)
+        .ok_or(not_found(format!("No register named `{}`", name)))
+}
+
+// An empty description given to edit removes it
+fn non_empty(text: String) -> Option<String> {
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+fn not_found(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::NotFound, message)
+}
+
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+pub fn decode_handler(args: DecodeArgs) -> Result<(), std::io::Error> {
+    let mut register_family = pull_existing_json(&args.path)?;
+    let register = find_register(&mut register_family, &args.register)?;
+    check_register_value(register, args.value)?;
+
+    let rows: Vec<Vec<String>> = register.fields
+        .iter()
+        .map(|field| {
+            let value = field.get_value(args.value);
+            let signed = field.get_signed_value(args.value);
+            // Enums are matched against what the get method returns
+            let returned = if field.negative.unwrap_or(false) { signed } else { value as i64 };
+            vec![
+                field.name.clone(),
+                format!("{}:{}", field.msb, field.lsb),
+                field.access().to_string(),
+                format!("{:#x}", value),
+                value.to_string(),
+                signed.to_string(),
+                field.enum_name(returned).unwrap_or("-").to_string(),
+            ]
+        })
+        .collect();
+
+    println!("{} ({}-bit) = {:#x}", register.name, register.size, args.value);
+    print!("{}", format_table(&["Field", "Bits", "Access", "Raw", "Unsigned", "Signed", "Enum"], &rows));
+    Ok(())
+}
+
+pub fn encode_handler(args: EncodeArgs) -> Result<(), std::io::Error> {
+    let mut register_family = pull_existing_json(&args.path)?;
+    let register = find_register(&mut register_family, &args.register)?;
+    check_register_value(register, args.initial)?;
+
+    let mut value = args.initial;
+    let mut rejected = Vec::new();
+    for assignment in &args.fields {
+        let (field_name, field_value) = assignment
+            .split_once('=')
+            .ok_or(invalid_input(format!("Expected field=value, got `{}`", assignment)))?;
+        let field = register.fields
+            .iter()
+            .find(|field| field.name == field_name)
+            .ok_or(not_found(format!("No field named `{}` in register `{}`", field_name, register.name)))?;
+
+        let number = match field.enums.iter().flatten().find(|e| e.name == field_value) {
+            Some(enum_value) => enum_value.value as i128,
+            None => parse_number(field_value).map_err(invalid_input)?,
+        };
+
+        if !field.write {
+            rejected.push(format!("{} has no set method", field.name));
+            continue;
+        }
+        match field.set_value(value, number) {
+            Some(new_value) => value = new_value,
+            None => {
+                let (min, max) = field.value_range();
+                rejected.push(format!("{} = {} is out of range {}..={}", field.name, field_value, min, max));
+            },
+        }
+    }
+
+    println!("{:#x}", value);
+    if rejected.is_empty() {
+        return Ok(());
+    }
+    for problem in &rejected {
+        eprintln!("  {}", problem);
+    }
+    Err(invalid_input(format!("{} field(s) were not set", rejected.len())))
+}
+
+fn check_register_value(register: &Register, value: u64) -> Result<(), std::io::Error> {
+    if register.size < 64 && value >> register.size != 0 {
+        return Err(invalid_input(format!("{:#x} doesn't fit in {}-bit register `{}`", value, register.size, register.name)));
+    }
+    Ok(())
+}
+
+pub fn trace_handler(args: TraceArgs) -> Result<(), std::io::Error> {
+    let pattern = regex::Regex::new(&args.pattern)
+        .map_err(|why| invalid_input(format!("Invalid pattern: {}", why)))?;
+    for group in ["address", "value"] {
+        if !pattern.capture_names().any(|name| name == Some(group)) {
+            return Err(invalid_input(format!("The pattern needs a named group `{}`", group)));
+        }
+    }
+
+    let families: Vec<RegisterFamily> = expand_family_paths(&args.path)?
+        .iter()
+        .map(pull_existing_json)
+        .collect::<Result<_, _>>()?;
+    let log = fs::read_to_string(&args.log)?;
+
+    let trace = trace_log(&families, &log, &pattern)
+        .map_err(|clashes| invalid_input(format!("Couldn't tell registers apart by address:\n  {}", clashes.join("\n  "))))?;
+    print!("{}", trace);
+    Ok(())
+}
+
+pub fn diff_handler(args: DiffArgs) -> Result<(), std::io::Error> {
+    let old = pull_existing_json(&args.old)?;
+    let new = pull_existing_json(&args.new)?;
+    let changes = diff_register_families(&old, &new);
+
+    match args.output_format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&changes)?),
+        OutputFormat::Text => {
+            let rows: Vec<Vec<String>> = changes
+                .iter()
+                .map(|change| {
+                    let compatibility = match change.compatibility {
+                        Compatibility::Breaking => "breaking",
+                        Compatibility::Compatible => "compatible",
+                    };
+                    vec![compatibility.to_string(), change.location.clone(), change.description.clone()]
+                })
+                .collect();
+            if !rows.is_empty() {
+                print!("{}", format_table(&["Compatibility", "Location", "Change"], &rows));
+            }
+            let breaking = changes.iter().filter(|change| change.compatibility == Compatibility::Breaking).count();
+            println!("{} change(s), {} breaking", changes.len(), breaking);
+        },
+    }
+    Ok(())
+}
+
+pub fn merge_handler(args: MergeArgs) -> Result<(), std::io::Error> {
+    let families = args.paths.iter().map(pull_existing_json).collect::<Result<_, _>>()?;
+    let mut merged = merge_register_families(families)
+        .map_err(|problems| invalid_input(format!("Couldn't merge:\n  {}", problems.join("\n  "))))?;
+    if let Some(name) = args.name {
+        merged.register_family = name;
+    }
+
+    write_register_family(&args.output, &merged)
+}
+
+pub fn split_handler(args: SplitArgs) -> Result<(), std::io::Error> {
+    // Never overwrite another family with the split off registers
+    if fs::metadata(&args.output).is_ok() {
+        return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, format!("{} already exists", args.output)));
+    }
+
+    let mut register_family = pull_existing_json(&args.path)?;
+    let new_family = split_register_family(&mut register_family, &args.registers, args.name)
+        .map_err(|problems| not_found(problems.join("\n")))?;
+
+    // Write the new family first so a failure can't lose the moved registers, once it's known the
+    // original can be rewritten so they can't end up in both
+    check_comments_kept(&args.path, ConfigFormat::of_path(&args.path))?;
+    write_register_family(&args.output, &new_family)?;
+    write_register_family(&args.path, &register_family)
+}
+
+pub fn fmt_handler(args: FmtArgs) -> Result<(), std::io::Error> {
+    let mut unformatted = Vec::new();
+    for path in expand_family_paths(&args.path)? {
+        let contents = fs::read_to_string(&path)?;
+        let register_family = pull_existing_json(&path)?;
+        let format = ConfigFormat::of_path(&path);
+        if contents == format_register_family(&register_family, format)? {
+            continue;
+        }
+        // Formatting would remove the comments, so they are left alone
+        if format.has_comments(&contents) && !discard_comments() {
+            println!("Skipped {}, which has comments", path);
+            continue;
+        }
+
+        if args.check {
+            unformatted.push(path);
+        } else {
+            write_register_family(&path, &register_family)?;
+            println!("Formatted {}", path);
+        }
+    }
 
-    match file.write_all(serde_json::to_string_pretty(&register_family).unwrap().as_bytes()) {
-        Ok(_) => {},
-        Err(why) => panic!("Couldn't write to {}: {}", args.path, why)
+    if unformatted.is_empty() {
+        return Ok(());
     }
+    for path in &unformatted {
+        eprintln!("  {}", path);
+    }
+    Err(invalid_input(format!("{} file(s) are not formatted, run fmt to fix them", unformatted.len())))
+}
 
+pub fn schema_handler() -> Result<(), std::io::Error> {
+    println!("{}", serde_json::to_string_pretty(&register_family_schema())?);
     Ok(())
 }
 
+pub fn convert_handler(args: ConvertArgs) -> Result<(), std::io::Error> {
+    // --format would apply to both files, which are meant to be in different formats
+    if format_override().is_some() {
+        return Err(invalid_input(String::from("convert takes --from and --to instead of --format")));
+    }
+    let from = args.from.or_else(|| ConfigFormat::from_extension(&args.path)).unwrap_or(ConfigFormat::Json);
+    let register_family = read_register_family_as(&args.path, from)?;
+    // The input isn't rewritten, but its comments don't make it into the output either
+    if !discard_comments() && from.has_comments(&fs::read_to_string(&args.path)?) {
+        return Err(invalid_input(format!("{} has comments, which converting it would lose. Pass --discard-comments to convert it anyway", args.path)));
+    }
+    let format = args.to
+        .or_else(|| ConfigFormat::from_extension(&args.output))
+        .ok_or(invalid_input(format!("Can't tell the format of {} from its extension, pass --to", args.output)))?;
+
+    write_register_family_as(&args.output, &register_family, format)
+}
+
+pub fn import_handler(args: ImportArgs) -> Result<(), std::io::Error> {
+    let rows = read_spreadsheet(&args.path, args.sheet.as_deref())?;
+    let registers = rows_to_registers(&rows, &ColumnMap::new(&args.column))
+        .map_err(|problems| invalid_input(format!("Couldn't import {}:\n  {}", args.path, problems.join("\n  "))))?;
+
+    write_imported_registers(&args.output, args.name, &args.path, registers)
+}
+
+pub fn import_c_handler(args: ImportCArgs) -> Result<(), std::io::Error> {
+    let mut sources = Vec::new();
+    for path in &args.path {
+        let contents = fs::read_to_string(path)
+            .map_err(|why| std::io::Error::new(why.kind(), format!("Couldn't read {}: {}", path, why)))?;
+        sources.push((path.clone(), contents));
+    }
+
+    let import = import_c_headers(&sources, &CImportOptions { size: args.size, prefix: args.prefix, bit_order: args.bit_order });
+    if import.registers.is_empty() {
+        return Err(not_found(format!("No register fields found in {}", args.path.join(", "))));
+    }
+    write_imported_registers(&args.output, args.name, &args.path[0], import.registers)?;
+
+    if import.review.is_empty() {
+        return Ok(());
+    }
+    match args.report {
+        Some(report) => {
+            fs::write(&report, import.review.join("\n") + "\n")
+                .map_err(|why| std::io::Error::new(why.kind(), format!("Couldn't write to {}: {}", report, why)))?;
+            println!("Wrote {} item(s) to review to {}", import.review.len(), report);
+        },
+        None => {
+            println!("{} item(s) to review:", import.review.len());
+            for item in &import.review {
+                println!("  {}", item);
+            }
+        },
+    }
+    Ok(())
+}
+
+// Updating an existing configuration keeps everything the import doesn't hold, a new one is named
+// after the imported file
+fn write_imported_registers(output: &String, name: Option<String>, source: &str, registers: Vec<Register>) -> Result<(), std::io::Error> {
+    let mut register_family = if fs::metadata(output).is_ok() {
+        pull_existing_json(output)?
+    } else {
+        let stem = std::path::Path::new(source).file_stem().unwrap_or_default().to_string_lossy().to_string();
+        RegisterFamily { register_family: stem, ..Default::default() }
+    };
+    if let Some(name) = name {
+        register_family.register_family = name;
+    }
+    register_family.registers = registers;
+
+    write_register_family(output, &register_family)
+}
+
+pub fn export_handler(args: ExportArgs) -> Result<(), std::io::Error> {
+    let register_family = pull_existing_json(&args.path)?;
+    write_spreadsheet(&args.output, &register_family_to_rows(&register_family, &ColumnMap::new(&args.column)))
+}
+
 pub fn generate_handler(args: GenerateArgs) -> Result<(), std::io::Error> {
-    let register_family = pull_existing_json(&args.path);
+    if args.mmio && !matches!(args.backend, Backend::Cpp) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "--mmio only applies to the cpp backend"));
+    }
+    let register_family = pull_existing_json(&args.path)?;
     // Generate the files
-    generate_files(&register_family);
+    match args.backend {
+        Backend::Cpp => generate_files(&register_family, args.mmio),
+        Backend::Html => generate_html(&register_family)?,
+        Backend::Markdown => generate_markdown(&register_family)?,
+        Backend::Asciidoc => generate_asciidoc(&register_family)?,
+        Backend::Svg => generate_svg(&register_family)?,
+        Backend::Wavedrom => generate_wavedrom(&register_family)?,
+        Backend::Linux => generate_linux_header(&register_family)?,
+        Backend::Python => generate_python(&register_family)?,
+    }
     Ok(())
 }
 
 pub fn bootstrap_handler(args: BootstrapArgs) -> Result<(), std::io::Error> {
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(args.path.clone())
-        .expect("Could not open or create JSON file!");
-
     let reg_family = RegisterFamily {
         register_family: args.name.clone(),
-        register_family_widths: Vec::new(),
-        registers: Vec::new(),
+        base_address: args.base_address,
+        description: args.description,
+        long_description: args.long_description,
+        ..Default::default()
     };
 
-    match file.write_all(serde_json::to_string_pretty(&reg_family).unwrap().as_bytes()) {
-        Ok(_) => {},
-        Err(why) => panic!("Couldn't write to {}: {}", args.path, why)
-    }
-
-    Ok(())
+    write_register_family(&args.path, &reg_family)
 }
 
 pub fn tui_handler(args: TuiArgs) -> Result<(), std::io::Error> {
+    // Load everything that can fail before taking over the terminal
+    let config = TuiConfig::load(args.preset.as_deref())?;
+    let paths = expand_family_paths(&args.path)?;
+    let app = App::new(paths, config)?;
+
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -110,9 +502,8 @@ pub fn tui_handler(args: TuiArgs) -> Result<(), std::io::Error> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // create app and run it
-    let app = App::new(args.path);
-    let res = run_app(&mut terminal, app);
+    // run the app
+    let res = run_app(&mut terminal, app, &mut TerminalEvents);
 
     // restore terminal
     disable_raw_mode()?;
@@ -123,5 +514,31 @@ pub fn tui_handler(args: TuiArgs) -> Result<(), std::io::Error> {
     )?;
     terminal.show_cursor()?;
 
-    return res;
+    res
+}
+
+// Directories are expanded to every configuration file directly inside them
+fn expand_family_paths(paths: &[String]) -> Result<Vec<String>, std::io::Error> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        let metadata = fs::metadata(path).map_err(|why| std::io::Error::new(why.kind(), format!("Couldn't read {}: {}", path, why)))?;
+        if !metadata.is_dir() {
+            expanded.push(path.clone());
+            continue;
+        }
+
+        let mut entries: Vec<String> = fs::read_dir(path)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|entry| entry.is_file() && ConfigFormat::from_extension(&entry.to_string_lossy()).is_some())
+            .map(|entry| entry.to_string_lossy().to_string())
+            .collect();
+        entries.sort();
+        expanded.append(&mut entries);
+    }
+
+    if expanded.is_empty() {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No register family configuration files found"));
+    }
+    Ok(expanded)
 }
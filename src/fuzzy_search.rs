@@ -0,0 +1,89 @@
+// Small fuzzy matcher used by the TUI search overlay. A query matches a candidate if all of its
+// characters appear in order (ignoring case). The score rewards matches that are consecutive, at
+// the start of a word, or at the start of the candidate, so "lnkcap" ranks "LinkCap" above
+// "LaneMaskCapture". Unmatched characters cost a little, so an exact match beats a longer one.
+
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 24;
+const WORD_START_BONUS: i64 = 20;
+const FIRST_CHARACTER_BONUS: i64 = 30;
+const GAP_PENALTY: i64 = 1;
+
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().flat_map(|c| c.to_lowercase()).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut query_index = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for (i, c) in candidate.iter().enumerate() {
+        if query_index == query.len() {
+            break;
+        }
+        if !c.to_lowercase().eq(query[query_index].to_lowercase()) {
+            continue;
+        }
+
+        score += MATCH_SCORE;
+        if i == 0 {
+            score += FIRST_CHARACTER_BONUS;
+        } else if is_word_start(&candidate, i) {
+            score += WORD_START_BONUS;
+        }
+        match previous_match {
+            Some(previous) if previous + 1 == i => score += CONSECUTIVE_BONUS,
+            Some(previous) => score -= GAP_PENALTY * (i - previous - 1) as i64,
+            None => score -= GAP_PENALTY * i as i64,
+        }
+
+        previous_match = Some(i);
+        query_index += 1;
+    }
+
+    match previous_match {
+        Some(previous) if query_index == query.len() => Some(score - GAP_PENALTY * (candidate.len() - previous - 1) as i64),
+        _ => None,
+    }
+}
+
+// A word starts after a separator or at a lower to upper case change, e.g. the C in LinkCap
+fn is_word_start(candidate: &[char], i: usize) -> bool {
+    let previous = candidate[i - 1];
+    let current = candidate[i];
+    !previous.is_alphanumeric() || (previous.is_lowercase() && current.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_exact_then_prefix_then_word_start_then_scattered() {
+        let exact = fuzzy_score("cap", "cap").unwrap();
+        let prefix = fuzzy_score("cap", "capture").unwrap();
+        let word_start = fuzzy_score("cap", "LinkCap").unwrap();
+        let scattered = fuzzy_score("cap", "scrap").unwrap();
+        assert!(exact > prefix, "{} <= {}", exact, prefix);
+        assert!(prefix > word_start, "{} <= {}", prefix, word_start);
+        assert!(word_start > scattered, "{} <= {}", word_start, scattered);
+    }
+
+    #[test]
+    fn ignores_case_and_needs_every_character_in_order() {
+        assert_eq!(fuzzy_score("LINKCAP", "LinkCap"), fuzzy_score("linkcap", "LinkCap"));
+        assert_eq!(fuzzy_score("pac", "cap"), None);
+        assert_eq!(fuzzy_score("caps", "cap"), None);
+        assert_eq!(fuzzy_score("", "cap"), Some(0));
+    }
+
+    #[test]
+    fn finds_word_starts_after_separators_and_case_changes() {
+        let candidate: Vec<char> = "link_capLinkCAP".chars().collect();
+        let starts: Vec<usize> = (1..candidate.len()).filter(|&i| is_word_start(&candidate, i)).collect();
+        assert_eq!(starts, [5, 8, 12]);
+    }
+}
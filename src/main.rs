@@ -1,8 +1,11 @@
+
 mod reg_gen;
 mod cli_structs;
 mod command_handlers;
 mod tui_handler;
 mod vertical_tab;
+mod fuzzy_search;
+mod tui_config;
 
 // Clap
 use clap::Parser;
@@ -10,15 +13,44 @@ use clap::Parser;
 // My crates
 use crate::cli_structs::*;
 use crate::command_handlers::*;
+use crate::reg_gen::format_handling::{set_discard_comments, set_format_override};
 
-fn main() -> Result<(), std::io::Error> {
+fn main() {
     // Get user input and dispatch
     let cli_input = Cli::parse();
-    return match cli_input.command {
+    if let Some(format) = cli_input.format {
+        set_format_override(format);
+    }
+    if cli_input.discard_comments {
+        set_discard_comments();
+    }
+    let result = match cli_input.command {
         Commands::AddRegister(args) => add_register_handler(args),
+        Commands::AddField(args) => add_field_handler(args),
+        Commands::RemoveRegister(args) => remove_register_handler(args),
+        Commands::RemoveField(args) => remove_field_handler(args),
+        Commands::Edit(args) => edit_handler(args),
+        Commands::Decode(args) => decode_handler(args),
+        Commands::Encode(args) => encode_handler(args),
+        Commands::Trace(args) => trace_handler(args),
+        Commands::Diff(args) => diff_handler(args),
+        Commands::Merge(args) => merge_handler(args),
+        Commands::Split(args) => split_handler(args),
+        Commands::Fmt(args) => fmt_handler(args),
+        Commands::Schema => schema_handler(),
+        Commands::Convert(args) => convert_handler(args),
+        Commands::Import(args) => import_handler(args),
+        Commands::Export(args) => export_handler(args),
+        Commands::ImportC(args) => import_c_handler(args),
         Commands::Generate(args) => generate_handler(args),
         Commands::Bootstrap(args) => bootstrap_handler(args),
         Commands::Tui(args) => tui_handler(args),
+    };
+
+    // Print errors as plain messages rather than their debug representation
+    if let Err(why) = result {
+        eprintln!("Error: {}", why);
+        std::process::exit(1);
     }
 }
 
@@ -0,0 +1,498 @@
+use clap::ValueEnum;
+
+use crate::reg_gen::c_import_handling::*;
+use crate::reg_gen::register::*;
+use crate::reg_gen::validation::SUPPORTED_REGISTER_WIDTHS;
+
+// Which end of the register the first declared bitfield is placed at. C leaves this to the ABI,
+// little endian ABIs such as GCC's and MSVC's on x86 and Arm allocate from the least significant bit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum BitOrder {
+    #[default]
+    LsbFirst,
+    MsbFirst,
+}
+
+// Words that make up a type rather than name a member
+const TYPE_KEYWORDS: [&str; 11] = ["unsigned", "signed", "char", "short", "int", "long", "bool", "_Bool", "volatile", "const", "enum"];
+
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+struct Aggregate {
+    union: bool,
+    tag: Option<String>,
+    members: Vec<Member>,
+}
+
+enum Member {
+    Bitfield { type_words: Vec<String>, name: Option<String>, width: Option<u64>, line: usize },
+    Plain { type_words: Vec<String>, name: String, line: usize },
+    Nested { aggregate: Aggregate, declarator: Option<String>, line: usize },
+}
+
+// Registers from structs of bitfields. A struct inside a union, like the README's, is named after
+// the union and sized by its raw member, and registers nested in a peripheral struct are named by
+// their member name.
+pub fn import_c_bitfields(sources: &[(String, String)], bit_order: BitOrder) -> CImport {
+    let mut import = CImport { registers: Vec::new(), review: Vec::new() };
+    for (file, contents) in sources {
+        let tokens = c_tokens(contents);
+        let mut position = 0;
+        let mut typedef = false;
+        while position < tokens.len() {
+            match tokens[position].text.as_str() {
+                "typedef" => {
+                    typedef = true;
+                    position += 1;
+                },
+                ";" => {
+                    typedef = false;
+                    position += 1;
+                },
+                "struct" | "union" => {
+                    let line = tokens[position].line;
+                    match parse_aggregate(&tokens, &mut position) {
+                        Some(aggregate) => {
+                            let declarator = declarator(&tokens, &mut position);
+                            let name = if typedef { declarator.or(aggregate.tag.clone()) } else { aggregate.tag.clone() };
+                            let mut context = Context { file, bit_order, import: &mut import };
+                            context.collect(&aggregate, name, None, line);
+                        },
+                        None => position += 1,
+                    }
+                },
+                _ => position += 1,
+            }
+        }
+    }
+    import
+}
+
+struct Context<'a> {
+    file: &'a str,
+    bit_order: BitOrder,
+    import: &'a mut CImport,
+}
+
+impl Context<'_> {
+    fn review(&mut self, line: usize, message: String) {
+        self.import.review.push(format!("{}:{}: {}", self.file, line, message));
+    }
+
+    // Finds the registers in an aggregate, given the name and size a register in it would have
+    fn collect(&mut self, aggregate: &Aggregate, name: Option<String>, raw_size: Option<u8>, line: usize) {
+        if aggregate.union {
+            // The raw member sizes the register, and the first struct of bitfields lays it out
+            let raw_size = aggregate.members.iter().find_map(|member| match member {
+                Member::Plain { type_words, line, .. } if is_long(type_words) => {
+                    self.review(*line, format!("union `{}` has a `long` raw member, which may be 32 or 64 bits, so it's sized from its fields", name.clone().unwrap_or_default()));
+                    None
+                },
+                Member::Plain { type_words, .. } => type_bits(type_words).map(|bits| bits as u8),
+                _ => None,
+            });
+            let mut views = 0;
+            for member in &aggregate.members {
+                let Member::Nested { aggregate: nested, declarator, line } = member else {
+                    continue;
+                };
+                if !has_bitfields(nested) {
+                    self.collect(nested, nested.tag.clone().or(declarator.clone()), None, *line);
+                    continue;
+                }
+                views += 1;
+                if views > 1 {
+                    self.review(*line, format!("only the first bitfield struct in union `{}` is imported", name.clone().unwrap_or_default()));
+                    continue;
+                }
+                self.collect(nested, name.clone(), raw_size, *line);
+            }
+            return;
+        }
+
+        if has_bitfields(aggregate) {
+            match name {
+                Some(name) => self.add_register(aggregate, name, raw_size, line),
+                None => self.review(line, String::from("anonymous bitfield struct, left out")),
+            }
+        }
+        for member in &aggregate.members {
+            if let Member::Nested { aggregate: nested, declarator, line } = member {
+                self.collect(nested, nested.tag.clone().or(declarator.clone()), None, *line);
+            }
+        }
+    }
+
+    fn add_register(&mut self, aggregate: &Aggregate, name: String, raw_size: Option<u8>, line: usize) {
+        // Bits are allocated from the lsb, without splitting a field across units of its type
+        let mut position: u64 = 0;
+        let mut widest_unit: u64 = 0;
+        let mut fields: Vec<(String, u64, u64, bool, usize)> = Vec::new();
+        for member in &aggregate.members {
+            let (type_words, member_name, width, member_line) = match member {
+                Member::Bitfield { width: None, line, .. } => {
+                    self.review(*line, format!("register `{}` has a field width that isn't a number, left out", name));
+                    return;
+                },
+                Member::Bitfield { type_words, name, width, line } => (type_words, name.as_ref(), *width, *line),
+                // Ordinary members take a whole unit of their type
+                Member::Plain { type_words, name, line } => (type_words, Some(name), None, *line),
+                Member::Nested { line, .. } => {
+                    self.review(*line, format!("register `{}` has a nested struct or union, left out", name));
+                    return;
+                },
+            };
+            if is_long(type_words) {
+                self.review(member_line, format!("register `{}` has a `{}` field, which may be 32 or 64 bits, left out", name, type_words.join(" ")));
+                return;
+            }
+            let unit = match type_bits(type_words) {
+                Some(unit) => unit,
+                None => {
+                    self.review(member_line, format!("unknown type `{}` in register `{}`, assuming 32 bits", type_words.join(" "), name));
+                    32
+                },
+            };
+            widest_unit = widest_unit.max(unit);
+            let width = match width {
+                Some(width) if width > unit => {
+                    self.review(member_line, format!("register `{}` has a {}-bit field wider than its type, left out", name, width));
+                    return;
+                },
+                Some(width) => width,
+                None => unit,
+            };
+
+            if width == 0 || position / unit != (position + width - 1) / unit {
+                position = position.div_ceil(unit) * unit;
+            }
+            if let Some(member_name) = member_name.filter(|member_name| !is_reserved(member_name)) {
+                fields.push((member_name.clone(), position, width, is_signed(type_words), member_line));
+            }
+            position += width;
+        }
+
+        let size = match raw_size {
+            Some(raw_size) if position > raw_size as u64 => {
+                self.review(line, format!("register `{}` has {} bits of fields, more than its {}-bit raw member, left out", name, position, raw_size));
+                return;
+            },
+            Some(raw_size) => raw_size,
+            None => match SUPPORTED_REGISTER_WIDTHS.iter().copied().find(|width| *width as u64 >= position.max(widest_unit)) {
+                Some(size) => size,
+                None => {
+                    self.review(line, format!("register `{}` has {} bits of fields, more than 64, left out", name, position));
+                    return;
+                },
+            },
+        };
+
+        let fields = fields
+            .into_iter()
+            .map(|(field_name, lsb, width, signed, _)| {
+                let lsb = match self.bit_order {
+                    BitOrder::LsbFirst => lsb,
+                    BitOrder::MsbFirst => size as u64 - lsb - width,
+                };
+                Field {
+                    name: field_name,
+                    lsb: lsb as u8,
+                    msb: (lsb + width - 1) as u8,
+                    read: true,
+                    write: true,
+                    negative: signed.then_some(true),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        if self.import.registers.iter().any(|register| register.name == name) {
+            self.review(line, format!("register `{}` is defined more than once, left out", name));
+            return;
+        }
+        self.import.registers.push(Register { name, size, fields, ..Default::default() });
+    }
+}
+
+fn has_bitfields(aggregate: &Aggregate) -> bool {
+    aggregate.members.iter().any(|member| matches!(member, Member::Bitfield { .. }))
+}
+
+// Padding such as `reserved : 1` is left as a gap
+fn is_reserved(name: &str) -> bool {
+    let lower = name.trim_start_matches('_').to_lowercase();
+    ["reserved", "rsvd", "resv"].iter().any(|prefix| lower.starts_with(prefix))
+}
+
+// Bits in a type, from the fixed width types and kernel style u32 names by their number
+fn type_bits(type_words: &[String]) -> Option<u64> {
+    let words: Vec<&str> = type_words.iter().map(String::as_str).filter(|word| !matches!(*word, "volatile" | "const" | "signed" | "unsigned")).collect();
+    match words.as_slice() {
+        [] | ["int"] | ["enum", _] => Some(32),
+        ["char"] | ["bool"] | ["_Bool"] => Some(8),
+        ["short"] | ["short", "int"] => Some(16),
+        ["long", "long"] | ["long", "long", "int"] => Some(64),
+        [name] => {
+            let digits: String = name.trim_end_matches("_t").chars().rev().take_while(|c| c.is_ascii_digit()).collect::<Vec<char>>().into_iter().rev().collect();
+            digits.parse().ok().filter(|bits| [8, 16, 32, 64].contains(bits))
+        },
+        _ => None,
+    }
+}
+
+// long is 32 bits on Windows and 32-bit targets, but 64 on LP64 targets like Linux, so its size
+// can't be known from the header
+fn is_long(type_words: &[String]) -> bool {
+    type_words.iter().filter(|word| *word == "long").count() == 1
+}
+
+// Fixed width signed types and plain int, which compilers treat as signed in bitfields
+fn is_signed(type_words: &[String]) -> bool {
+    if type_words.iter().any(|word| word == "unsigned") {
+        return false;
+    }
+    type_words.iter().any(|word| {
+        word == "signed" || word == "int" || word == "short" || word == "long" || (word.starts_with("int") && word.ends_with("_t")) || (word.starts_with('s') && word[1..].parse::<u8>().is_ok())
+    })
+}
+
+// After the closing brace: the name being declared, skipping pointers and stopping at the ;
+fn declarator(tokens: &[Token], position: &mut usize) -> Option<String> {
+    let mut name = None;
+    while let Some(token) = tokens.get(*position) {
+        *position += 1;
+        match token.text.as_str() {
+            ";" => break,
+            text if name.is_none() && is_identifier(text) => name = Some(text.to_string()),
+            _ => (),
+        }
+    }
+    name
+}
+
+// Parses `struct tag { ... }` from its keyword, leaving position after the closing brace. None
+// for a use of the type without a body.
+fn parse_aggregate(tokens: &[Token], position: &mut usize) -> Option<Aggregate> {
+    let start = *position;
+    let union = tokens[start].text == "union";
+    let mut next = start + 1;
+    let tag = tokens.get(next).filter(|token| is_identifier(&token.text)).map(|token| token.text.clone());
+    if tag.is_some() {
+        next += 1;
+    }
+    if tokens.get(next)?.text != "{" {
+        return None;
+    }
+    *position = next + 1;
+
+    let mut members = Vec::new();
+    while let Some(token) = tokens.get(*position) {
+        match token.text.as_str() {
+            "}" => {
+                *position += 1;
+                return Some(Aggregate { union, tag, members });
+            },
+            "struct" | "union" => {
+                let line = token.line;
+                let before = *position;
+                if let Some(aggregate) = parse_aggregate(tokens, position) {
+                    let declarator = declarator(tokens, position);
+                    members.push(Member::Nested { aggregate, declarator, line });
+                } else {
+                    *position = before;
+                    members.extend(parse_declaration(tokens, position));
+                }
+            },
+            _ => members.extend(parse_declaration(tokens, position)),
+        }
+    }
+    None
+}
+
+// One declaration up to its ;, which may declare several members like `uint32_t a : 4, b : 4;`
+fn parse_declaration(tokens: &[Token], position: &mut usize) -> Vec<Member> {
+    let mut declaration = Vec::new();
+    while let Some(token) = tokens.get(*position) {
+        *position += 1;
+        match token.text.as_str() {
+            ";" => break,
+            "}" => {
+                *position -= 1;
+                break;
+            },
+            _ => declaration.push(token),
+        }
+    }
+
+    let mut members = Vec::new();
+    let mut type_words: Vec<String> = Vec::new();
+    for (index, declarator) in declaration.split(|token| token.text == ",").enumerate() {
+        let Some(first) = declarator.first() else {
+            continue;
+        };
+        let (before, width) = match declarator.iter().position(|token| token.text == ":") {
+            Some(colon) => (&declarator[..colon], Some(&declarator[colon + 1..])),
+            None => (declarator, None),
+        };
+        let mut words: Vec<String> = before.iter().map(|token| token.text.clone()).filter(|word| is_identifier(word)).collect();
+
+        // Only the first declarator carries the type, and a lone type is unnamed padding
+        let name = if index == 0 {
+            let named = words.len() >= 2 && !TYPE_KEYWORDS.contains(&words[words.len() - 1].as_str());
+            let name = if named { words.pop() } else { None };
+            type_words = words;
+            name
+        } else {
+            words.pop()
+        };
+
+        match width {
+            Some(width) => {
+                let width = match width {
+                    [token] => parse_literal(&token.text),
+                    _ => None,
+                };
+                members.push(Member::Bitfield { type_words: type_words.clone(), name, width, line: first.line });
+            },
+            None => {
+                if let Some(name) = name {
+                    members.push(Member::Plain { type_words: type_words.clone(), name, line: first.line });
+                }
+            },
+        }
+    }
+    members
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Identifiers, numbers and single punctuation characters, without comments, preprocessor lines
+// and string literals
+fn c_tokens(contents: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let stripped = strip_comments(contents);
+    let mut continued = false;
+    for (index, line) in stripped.lines().enumerate() {
+        let preprocessor = continued || line.trim_start().starts_with('#');
+        continued = preprocessor && line.ends_with('\\');
+        if preprocessor {
+            continue;
+        }
+
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            let length = if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+                rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len())
+            } else if rest.starts_with(['"', '\'']) {
+                let quote = rest.chars().next().unwrap();
+                rest[1..].find(quote).map(|end| end + 2).unwrap_or(rest.len())
+            } else {
+                rest.chars().next().unwrap().len_utf8()
+            };
+            tokens.push(Token { text: rest[..length].to_string(), line: index + 1 });
+            rest = rest[length..].trim_start();
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "\
+#include <stdint.h>
+
+// From the README
+union link_capabilities_register {
+  uint32_t raw;
+  struct {
+    uint32_t max_link_speed : 4;
+    uint32_t max_link_width : 6;
+    uint32_t aspm_support : 2;
+    uint32_t l0s_exit_latency : 3, l1_exit_latency : 3;
+    uint32_t reserved : 6;
+    uint32_t port_number : 8;
+  } data;
+};
+
+typedef struct {
+  int8_t offset : 5;
+  uint8_t : 0;
+  uint16_t count : 12; /* doesn't fit after offset */
+} adc_t;
+
+struct uart_regs {
+  union { volatile uint32_t raw; struct { uint32_t en : 1; } bits; } CR;
+  volatile uint32_t DR;
+};
+";
+
+    fn bits(register: &Register) -> Vec<(&str, u8, u8)> {
+        register.fields.iter().map(|field| (field.name.as_str(), field.msb, field.lsb)).collect()
+    }
+
+    #[test]
+    fn lays_out_bitfields_from_the_lsb() {
+        let import = import_c_bitfields(&[(String::from("regs.h"), String::from(HEADER))], BitOrder::LsbFirst);
+        assert!(import.review.is_empty(), "{:?}", import.review);
+        let names: Vec<(&str, u8)> = import.registers.iter().map(|register| (register.name.as_str(), register.size)).collect();
+        assert_eq!(names, [("link_capabilities_register", 32), ("adc_t", 32), ("CR", 32)]);
+
+        assert_eq!(bits(&import.registers[0]), [
+            ("max_link_speed", 3, 0),
+            ("max_link_width", 9, 4),
+            ("aspm_support", 11, 10),
+            ("l0s_exit_latency", 14, 12),
+            ("l1_exit_latency", 17, 15),
+            ("port_number", 31, 24),
+        ]);
+        assert_eq!(bits(&import.registers[1]), [("offset", 4, 0), ("count", 27, 16)]);
+        assert_eq!(import.registers[1].fields[0].negative, Some(true));
+        assert_eq!(bits(&import.registers[2]), [("en", 0, 0)]);
+    }
+
+    #[test]
+    fn lays_out_bitfields_from_the_msb() {
+        let import = import_c_bitfields(&[(String::from("regs.h"), String::from(HEADER))], BitOrder::MsbFirst);
+        assert_eq!(bits(&import.registers[0])[0], ("max_link_speed", 31, 28));
+        assert_eq!(bits(&import.registers[0])[5], ("port_number", 7, 0));
+    }
+
+    fn review(header: &str) -> Vec<String> {
+        import_c_bitfields(&[(String::from("regs.h"), String::from(header))], BitOrder::LsbFirst).review
+    }
+
+    #[test]
+    fn reviews_long_instead_of_guessing_its_size() {
+        assert_eq!(
+            review("struct ctrl {\n  unsigned long en : 1;\n};\n"),
+            ["regs.h:2: register `ctrl` has a `unsigned long` field, which may be 32 or 64 bits, left out"]
+        );
+        let import = import_c_bitfields(&[(String::from("regs.h"), String::from("union ctrl {\n  unsigned long raw;\n  struct { uint8_t en : 1; } bits;\n};\n"))], BitOrder::LsbFirst);
+        assert_eq!(import.review, ["regs.h:2: union `ctrl` has a `long` raw member, which may be 32 or 64 bits, so it's sized from its fields"]);
+        assert_eq!(import.registers[0].size, 8);
+        // long long is always 64 bits
+        assert!(review("struct ctrl {\n  unsigned long long en : 40;\n};\n").is_empty());
+    }
+
+    #[test]
+    fn reviews_registers_that_cant_be_laid_out() {
+        assert_eq!(review("struct {\n  uint32_t en : 1;\n} ctrl;\n"), ["regs.h:1: anonymous bitfield struct, left out"]);
+        assert_eq!(
+            review("struct ctrl {\n  uint32_t en : 1;\n  struct { uint32_t a : 1; } inner;\n};\n"),
+            ["regs.h:3: register `ctrl` has a nested struct or union, left out"]
+        );
+        assert_eq!(review("struct ctrl {\n  uint8_t count : 9;\n};\n"), ["regs.h:2: register `ctrl` has a 9-bit field wider than its type, left out"]);
+        assert_eq!(
+            review("union ctrl {\n  uint8_t raw;\n  struct {\n    uint16_t a : 6;\n    uint16_t b : 6;\n  } bits;\n};\n"),
+            ["regs.h:3: register `ctrl` has 12 bits of fields, more than its 8-bit raw member, left out"]
+        );
+    }
+}
@@ -0,0 +1,672 @@
+use std::collections::HashMap;
+
+use crate::reg_gen::bitfield_import_handling::*;
+use crate::reg_gen::register::*;
+use crate::reg_gen::validation::SUPPORTED_REGISTER_WIDTHS;
+
+// Macro name suffixes of each part of a field, covering Linux, CMSIS and TivaWare style headers
+const MASK_SUFFIXES: [&str; 4] = ["_MASK", "_MSK", "_Msk", "_M"];
+const SHIFT_SUFFIXES: [&str; 6] = ["_SHIFT", "_SHFT", "_SFT", "_POS", "_Pos", "_S"];
+const WIDTH_SUFFIXES: [&str; 4] = ["_WIDTH", "_Width", "_LEN", "_BITS"];
+const OFFSET_SUFFIXES: [&str; 3] = ["_OFFSET", "_OFS", "_REG"];
+
+// How deeply macros may refer to other macros, guarding against cycles
+const MAX_MACRO_DEPTH: usize = 16;
+
+pub struct CImportOptions {
+    // Size of registers whose fields all fit in it
+    pub size: u8,
+    // Stripped from the start of every macro name
+    pub prefix: Option<String>,
+    pub bit_order: BitOrder,
+}
+
+pub struct CImport {
+    pub registers: Vec<Register>,
+    // Everything that couldn't be imported with confidence, by file and line
+    pub review: Vec<String>,
+}
+
+// A #define without parameters
+struct Macro {
+    name: String,
+    body: String,
+    location: String,
+}
+
+#[derive(Default)]
+struct FieldParts {
+    mask: Option<u64>,
+    shift: Option<u64>,
+    width: Option<u64>,
+    location: String,
+}
+
+type PartOf = fn(&mut FieldParts) -> &mut Option<u64>;
+
+// Registers from both the macros and the bitfield structs of the headers
+pub fn import_c_headers(sources: &[(String, String)], options: &CImportOptions) -> CImport {
+    let mut import = import_c_macros(sources, options);
+    let bitfields = import_c_bitfields(sources, options.bit_order);
+    import.review.extend(bitfields.review);
+    for register in bitfields.registers {
+        if import.registers.iter().any(|existing| existing.name == register.name) {
+            import.review.push(format!("register `{}` is defined by both macros and a bitfield struct, using the macros", register.name));
+        } else {
+            import.registers.push(register);
+        }
+    }
+    import
+}
+
+// Infers registers and fields from mask, shift and width macros. Field macros are grouped into
+// the register whose offset macro is the longest prefix of their name, like `UART_CR` for
+// `UART_CR_EN_MASK`, or by their common prefix when there is none. Numbers named after a field,
+// like `UART_CR_MODE_FAST`, become its enum values.
+pub fn import_c_macros(sources: &[(String, String)], options: &CImportOptions) -> CImport {
+    let macros: Vec<Macro> = sources.iter().flat_map(|(file, contents)| object_macros(file, contents)).collect();
+    let bodies: HashMap<&str, &str> = macros.iter().map(|m| (m.name.as_str(), m.body.as_str())).collect();
+    let mut review = Vec::new();
+
+    // Sort each macro into parts of fields, register offsets and plain numbers, each kept with the
+    // index of its macro
+    let mut field_order: Vec<String> = Vec::new();
+    let mut parts: HashMap<String, FieldParts> = HashMap::new();
+    let mut bare_masks: Vec<(String, u64, usize)> = Vec::new();
+    let mut offsets: Vec<(String, u64, usize)> = Vec::new();
+    let mut numbers: Vec<(String, u64, usize)> = Vec::new();
+    for (index, m) in macros.iter().enumerate() {
+        // Strings, types and the like aren't worth reviewing
+        let Some(value) = evaluate(&m.body, &bodies, 0) else {
+            continue;
+        };
+        let name = match &options.prefix {
+            Some(prefix) => m.name.strip_prefix(prefix.as_str()).unwrap_or(&m.name),
+            None => &m.name,
+        };
+
+        let (base, kind, part): (&str, &str, PartOf) =
+            if let Some(base) = strip_suffix(name, &MASK_SUFFIXES) {
+                (base, "masks", |parts| &mut parts.mask)
+            } else if let Some(base) = strip_suffix(name, &SHIFT_SUFFIXES) {
+                (base, "shifts", |parts| &mut parts.shift)
+            } else if let Some(base) = strip_suffix(name, &WIDTH_SUFFIXES) {
+                (base, "widths", |parts| &mut parts.width)
+            } else if let Some(base) = strip_suffix(name, &OFFSET_SUFFIXES).filter(|_| !value.shifted) {
+                // A mask is a field that happens to be called offset
+                offsets.push((base.to_string(), value.number, index));
+                continue;
+            } else if value.shifted {
+                // BIT(), GENMASK() and anything else built with a shift, sorted out once every
+                // field is known
+                bare_masks.push((name.to_string(), value.number, index));
+                continue;
+            } else {
+                numbers.push((name.to_string(), value.number, index));
+                continue;
+            };
+        let field_parts = field_parts(&mut parts, &mut field_order, base, &m.location);
+        set_part(part(field_parts), value.number, base, kind, &m.location, &mut review);
+    }
+
+    // A bare mask is a field, unless it's named after another field like `UART_CR_MODE_FAST`, when
+    // it's one of that field's values already shifted into place
+    for (name, mask, index) in &bare_masks {
+        let is_value = field_order.iter().chain(bare_masks.iter().map(|(other, ..)| other)).any(|base| is_prefix(base, name));
+        if is_value {
+            numbers.push((name.clone(), *mask, *index));
+        } else {
+            let field_parts = field_parts(&mut parts, &mut field_order, name, &macros[*index].location);
+            set_part(&mut field_parts.mask, *mask, name, "masks", &macros[*index].location, &mut review);
+        }
+    }
+    numbers.sort_by_key(|(_, _, index)| *index);
+
+    // Work out the bits of every field
+    let mut fields: Vec<(String, u8, u8, String)> = Vec::new();
+    for base in &field_order {
+        let field_parts = &parts[base];
+        match field_bits(field_parts) {
+            Ok(Some((msb, lsb))) => fields.push((base.clone(), msb, lsb, field_parts.location.clone())),
+            Ok(None) => (),
+            Err(why) => review.push(format!("{}: `{}` {}, left out", field_parts.location, base, why)),
+        }
+    }
+
+    // Registers are named by offset macros, and plain numbers that prefix a field's name
+    let mut candidates: Vec<&(String, u64, usize)> = offsets
+        .iter()
+        .chain(numbers.iter().filter(|(name, ..)| fields.iter().any(|(base, ..)| is_prefix(name, base))))
+        .collect();
+    candidates.sort_by_key(|(_, _, index)| *index);
+    let mut registers: Vec<Register> = Vec::new();
+    let mut locations: Vec<String> = Vec::new();
+    for (name, offset, index) in candidates {
+        let location = &macros[*index].location;
+        match registers.iter().find(|register| &register.name == name) {
+            Some(register) if register.offset != Some(*offset) => review.push(format!(
+                "{}: register `{}` was already given offset {:#x}, not {:#x}", location, name, register.offset.unwrap_or_default(), offset
+            )),
+            Some(_) => (),
+            None => {
+                registers.push(Register { name: name.clone(), offset: Some(*offset), ..Default::default() });
+                locations.push(location.clone());
+            },
+        }
+    }
+
+    for (base, msb, lsb, location) in &fields {
+        let register = registers
+            .iter()
+            .enumerate()
+            .filter(|(_, register)| is_prefix(&register.name, base))
+            .max_by_key(|(_, register)| register.name.len())
+            .map(|(index, _)| index);
+        let register = match register {
+            Some(index) => index,
+            None => {
+                // Group fields without a register by the longest prefix they share with another
+                // field, or else everything before the last underscore
+                let prefixes: Vec<&str> = base.match_indices('_').map(|(index, _)| &base[..index]).filter(|prefix| !prefix.is_empty()).collect();
+                let Some(last) = prefixes.last() else {
+                    review.push(format!("{}: `{}` can't be split into a register and field name, left out", location, base));
+                    continue;
+                };
+                let shared = prefixes
+                    .iter()
+                    .rev()
+                    .find(|prefix| fields.iter().filter(|(other, ..)| is_prefix(prefix, other)).count() >= 2)
+                    .unwrap_or(last);
+                match registers.iter().position(|register| register.name == *shared) {
+                    Some(index) => index,
+                    None => {
+                        review.push(format!("{}: no offset macro for register `{}`, inferred from the field names", location, shared));
+                        registers.push(Register { name: shared.to_string(), ..Default::default() });
+                        locations.push(location.clone());
+                        registers.len() - 1
+                    },
+                }
+            },
+        };
+
+        let register = &mut registers[register];
+        let name = &base[register.name.len() + 1..];
+        if let Some(other) = register.fields.iter().find(|other| *lsb <= other.msb && other.lsb <= *msb) {
+            review.push(format!(
+                "{}: field `{}.{}` at bits {}:{} overlaps `{}.{}`, left out", location, register.name, name, msb, lsb, register.name, other.name
+            ));
+            continue;
+        }
+        register.fields.push(Field { name: name.to_string(), lsb: *lsb, msb: *msb, read: true, write: true, ..Default::default() });
+    }
+
+    // Numbers named after a field are its values, either in place or already shifted
+    for (name, value, index) in &numbers {
+        let owner = registers
+            .iter_mut()
+            .flat_map(|register| {
+                let register_name = register.name.clone();
+                register.fields.iter_mut().map(move |field| (format!("{}_{}", register_name, field.name), field))
+            })
+            .filter(|(base, _)| is_prefix(base, name))
+            .max_by_key(|(base, _)| base.len());
+        let Some((base, field)) = owner else {
+            continue;
+        };
+        let max = field.get_value(u64::MAX);
+        let value = if *value > max && value & !(max << field.lsb) == 0 { value >> field.lsb } else { *value };
+        if value > max {
+            review.push(format!("{}: `{}` is {:#x}, too wide for field `{}`, left out", macros[*index].location, name, value, base));
+            continue;
+        }
+        field.enums.get_or_insert_with(Vec::new).push(EnumValue { name: name[base.len() + 1..].to_string(), value: value as i64 });
+    }
+
+    let mut seen_offsets: HashMap<u64, String> = HashMap::new();
+    for (register, location) in registers.iter_mut().zip(&locations) {
+        if let Some(offset) = register.offset {
+            if let Some(other) = seen_offsets.insert(offset, register.name.clone()) {
+                review.push(format!("{}: register `{}` has the same offset {:#x} as `{}`, offset left out", location, register.name, offset, other));
+                register.offset = None;
+            }
+        }
+
+        register.fields.sort_by_key(|field| (field.lsb, field.msb));
+        let needed = register.fields.iter().map(|field| field.msb + 1).max().unwrap_or(0);
+        register.size = if needed <= options.size {
+            options.size
+        } else {
+            let size = SUPPORTED_REGISTER_WIDTHS.iter().copied().find(|width| *width >= needed).unwrap_or(64);
+            review.push(format!("{}: register `{}` has fields up to bit {}, so it is {}-bit", location, register.name, needed - 1, size));
+            size
+        };
+    }
+
+    CImport { registers, review }
+}
+
+fn field_parts<'a>(parts: &'a mut HashMap<String, FieldParts>, order: &mut Vec<String>, base: &str, location: &str) -> &'a mut FieldParts {
+    if !parts.contains_key(base) {
+        order.push(base.to_string());
+    }
+    parts.entry(base.to_string()).or_insert_with(|| FieldParts { location: location.to_string(), ..Default::default() })
+}
+
+fn is_prefix(prefix: &str, name: &str) -> bool {
+    name.len() > prefix.len() + 1 && name.starts_with(prefix) && name.as_bytes()[prefix.len()] == b'_'
+}
+
+fn strip_suffix<'a>(name: &'a str, suffixes: &[&str]) -> Option<&'a str> {
+    suffixes.iter().find_map(|suffix| name.strip_suffix(suffix)).filter(|base| !base.is_empty())
+}
+
+fn set_part(part: &mut Option<u64>, value: u64, base: &str, kind: &str, location: &str, review: &mut Vec<String>) {
+    match part {
+        Some(existing) if *existing != value => review.push(format!("{}: `{}` has {} {:#x} and {:#x}, using the first", location, base, kind, existing, value)),
+        Some(_) => (),
+        None => *part = Some(value),
+    }
+}
+
+// The msb and lsb of a field, or None for a lone width which is as likely a buffer length
+fn field_bits(parts: &FieldParts) -> Result<Option<(u8, u8)>, String> {
+    let (lsb, width) = match (parts.mask, parts.shift, parts.width) {
+        (Some(0), _, _) => return Err(String::from("has an empty mask")),
+        (Some(mask), shift, width) => {
+            let mask_lsb = mask.trailing_zeros() as u64;
+            let run = mask >> mask_lsb;
+            if run & run.wrapping_add(1) != 0 {
+                return Err(format!("has mask {:#x}, which isn't a contiguous run of bits", mask));
+            }
+            let mask_width = run.count_ones() as u64;
+            if width.is_some_and(|width| width != mask_width) {
+                return Err(format!("has mask {:#x} but width {}", mask, width.unwrap()));
+            }
+            match shift {
+                None => (mask_lsb, mask_width),
+                Some(shift) if shift == mask_lsb => (shift, mask_width),
+                // An unshifted mask
+                Some(shift) if mask_lsb == 0 => (shift, mask_width),
+                Some(shift) => return Err(format!("has mask {:#x}, which doesn't start at its shift {}", mask, shift)),
+            }
+        },
+        (None, Some(shift), Some(width)) => (shift, width),
+        (None, Some(shift), None) => return Err(format!("has a shift of {} but no mask or width", shift)),
+        (None, None, _) => return Ok(None),
+    };
+    if width == 0 || lsb >= 64 || lsb.checked_add(width).is_none_or(|end| end > 64) {
+        return Err(format!("doesn't fit in 64 bits with lsb {} and width {}", lsb, width));
+    }
+    Ok(Some(((lsb + width - 1) as u8, lsb as u8)))
+}
+
+// Object-like #defines with comments removed and continuation lines joined
+fn object_macros(file: &str, contents: &str) -> Vec<Macro> {
+    let mut macros = Vec::new();
+    let stripped = strip_comments(contents);
+    let mut lines = stripped.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let mut line = line.to_string();
+        while line.ends_with('\\') {
+            line.pop();
+            match lines.next() {
+                Some((_, next)) => line.push_str(next),
+                None => break,
+            }
+        }
+
+        let Some(directive) = line.trim_start().strip_prefix('#') else {
+            continue;
+        };
+        let Some(definition) = directive.trim_start().strip_prefix("define") else {
+            continue;
+        };
+        if !definition.starts_with([' ', '\t']) {
+            continue;
+        }
+        let definition = definition.trim_start();
+        let name_length = definition.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(definition.len());
+        let (name, body) = definition.split_at(name_length);
+        // Function-like macros take arguments we can't know
+        if name.is_empty() || body.starts_with('(') || body.trim().is_empty() {
+            continue;
+        }
+        macros.push(Macro { name: name.to_string(), body: body.trim().to_string(), location: format!("{}:{}", file, index + 1) });
+    }
+    macros
+}
+
+// Keeps newlines so line numbers stay right
+pub fn strip_comments(contents: &str) -> String {
+    let mut stripped = String::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.peek().is_some_and(|next| *next != '\n') {
+                    chars.next();
+                }
+            },
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        stripped.push('\n');
+                    }
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+                stripped.push(' ');
+            },
+            ('"', _) => {
+                stripped.push(c);
+                while let Some(next) = chars.next() {
+                    stripped.push(next);
+                    if next == '\\' {
+                        stripped.extend(chars.next());
+                    } else if next == '"' || next == '\n' {
+                        break;
+                    }
+                }
+            },
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Value {
+    number: u64,
+    // Built with BIT(), GENMASK() or <<, so a mask rather than an offset or value
+    shifted: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(u64),
+    Identifier(String),
+    Operator(&'static str),
+}
+
+const OPERATORS: [&str; 13] = ["<<", ">>", "(", ")", ",", "~", "|", "&", "^", "+", "-", "*", "/"];
+
+// Evaluates the integer expressions found in register headers, or None for anything else
+fn evaluate(body: &str, macros: &HashMap<&str, &str>, depth: usize) -> Option<Value> {
+    if depth > MAX_MACRO_DEPTH {
+        return None;
+    }
+    let tokens = tokenize(body)?;
+    let mut parser = Parser { tokens: &tokens, position: 0, macros, depth };
+    let value = parser.expression()?;
+    (parser.position == tokens.len()).then_some(value)
+}
+
+fn tokenize(body: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = body.trim_start();
+    while !rest.is_empty() {
+        let length = if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            let length = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_literal(&rest[..length])?));
+            length
+        } else if rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            let length = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            tokens.push(Token::Identifier(rest[..length].to_string()));
+            length
+        } else {
+            let operator = OPERATORS.iter().find(|operator| rest.starts_with(**operator))?;
+            tokens.push(Token::Operator(operator));
+            operator.len()
+        };
+        rest = rest[length..].trim_start();
+    }
+    Some(tokens)
+}
+
+// C integer literals, with any U and L suffixes
+pub fn parse_literal(literal: &str) -> Option<u64> {
+    let digits = literal.trim_end_matches(['u', 'U', 'l', 'L']);
+    let lower = digits.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        u64::from_str_radix(binary, 2).ok()
+    } else if lower.len() > 1 && lower.starts_with('0') {
+        u64::from_str_radix(&lower[1..], 8).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    macros: &'a HashMap<&'a str, &'a str>,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn accept(&mut self, operator: &'static str) -> bool {
+        if self.peek() == Some(&Token::Operator(operator)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Binary operators from lowest to highest precedence
+    fn expression(&mut self) -> Option<Value> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Option<Value> {
+        const LEVELS: [&[&str]; 5] = [&["|"], &["^"], &["&"], &["<<", ">>"], &["+", "-"]];
+        let Some(operators) = LEVELS.get(level) else {
+            return self.product();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(operator) = operators.iter().find(|operator| self.peek() == Some(&Token::Operator(operator))) {
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            let number = match *operator {
+                "|" => left.number | right.number,
+                "^" => left.number ^ right.number,
+                "&" => left.number & right.number,
+                "<<" => left.number.checked_shl(u32::try_from(right.number).ok()?)?,
+                ">>" => left.number.checked_shr(u32::try_from(right.number).ok()?)?,
+                "+" => left.number.wrapping_add(right.number),
+                _ => left.number.wrapping_sub(right.number),
+            };
+            left = Value { number, shifted: left.shifted || right.shifted || *operator == "<<" };
+        }
+        Some(left)
+    }
+
+    fn product(&mut self) -> Option<Value> {
+        let mut left = self.unary()?;
+        loop {
+            if self.accept("*") {
+                let right = self.unary()?;
+                left = Value { number: left.number.wrapping_mul(right.number), shifted: left.shifted || right.shifted };
+            } else if self.accept("/") {
+                let right = self.unary()?;
+                left = Value { number: left.number.checked_div(right.number)?, shifted: left.shifted || right.shifted };
+            } else {
+                return Some(left);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Option<Value> {
+        if self.accept("~") {
+            let value = self.unary()?;
+            return Some(Value { number: !value.number, ..value });
+        }
+        if self.accept("-") {
+            let value = self.unary()?;
+            return Some(Value { number: value.number.wrapping_neg(), ..value });
+        }
+        if self.accept("+") {
+            return self.unary();
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Option<Value> {
+        match self.peek()?.clone() {
+            Token::Number(number) => {
+                self.position += 1;
+                Some(Value { number, shifted: false })
+            },
+            Token::Operator("(") => {
+                self.position += 1;
+                if self.skip_cast() {
+                    return self.unary();
+                }
+                let value = self.expression()?;
+                self.accept(")").then_some(value)
+            },
+            Token::Identifier(name) => {
+                self.position += 1;
+                if self.accept("(") {
+                    let mut arguments = vec![self.expression()?];
+                    while self.accept(",") {
+                        arguments.push(self.expression()?);
+                    }
+                    if !self.accept(")") {
+                        return None;
+                    }
+                    return call(&name, &arguments);
+                }
+                let body = self.macros.get(name.as_str())?;
+                evaluate(body, self.macros, self.depth + 1)
+            },
+            Token::Operator(_) => None,
+        }
+    }
+
+    // Casts like (uint32_t) or (unsigned long), just after their opening parenthesis
+    fn skip_cast(&mut self) -> bool {
+        let mut end = self.position;
+        while let Some(Token::Identifier(name)) = self.tokens.get(end) {
+            if self.macros.contains_key(name.as_str()) {
+                return false;
+            }
+            end += 1;
+        }
+        if end == self.position || self.tokens.get(end) != Some(&Token::Operator(")")) {
+            return false;
+        }
+        self.position = end + 1;
+        true
+    }
+}
+
+// The helper macros register headers build masks from
+fn call(name: &str, arguments: &[Value]) -> Option<Value> {
+    let shifted = |number: u64| Some(Value { number, shifted: true });
+    match (name, arguments) {
+        ("BIT" | "BIT_ULL" | "_BITUL" | "_BITULL", [bit]) => shifted(1u64.checked_shl(u32::try_from(bit.number).ok()?)?),
+        ("GENMASK" | "GENMASK_ULL", [msb, lsb]) if lsb.number <= msb.number && msb.number < 64 => {
+            shifted((u64::MAX >> (63 - msb.number)) & (u64::MAX << lsb.number))
+        },
+        ("U" | "UL" | "ULL" | "_U" | "_UL" | "_ULL" | "_AC", [value, ..]) => Some(*value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "\
+#ifndef UART_H
+#define UART_H
+
+#define UART_CR                 0x04 /* Control */
+#define UART_CR_EN              BIT(0)
+#define UART_CR_MODE            GENMASK(5, 4)
+#define UART_CR_MODE_SLOW       0x1
+#define UART_CR_MODE_FAST       (2 << 4)
+#define UART_CR_BAUD_MASK       0xFFu   // Unshifted, placed by the shift
+#define UART_CR_BAUD_SHIFT      8
+
+#define UART_SR_OFFSET          0x08
+#define UART_SR_ERR_Pos         (3U)
+#define UART_SR_ERR_Msk         (0x7UL << UART_SR_ERR_Pos)
+#define UART_SR_ODD_MASK        0x05
+#define UART_SR_TX_SHIFT        12
+
+#define DMA_STAT_BUSY_SHIFT     2
+#define DMA_STAT_BUSY_WIDTH     2
+#define DMA_STAT_DONE_MASK      \\
+    ((uint32_t)1 << 31)
+#endif
+";
+
+    fn import() -> CImport {
+        import_c_macros(&[(String::from("uart.h"), String::from(HEADER))], &CImportOptions { size: 32, prefix: None, bit_order: BitOrder::LsbFirst })
+    }
+
+    fn bits(register: &Register) -> Vec<(&str, u8, u8)> {
+        register.fields.iter().map(|field| (field.name.as_str(), field.msb, field.lsb)).collect()
+    }
+
+    #[test]
+    fn infers_registers_and_fields_from_macros() {
+        let import = import();
+        let names: Vec<(&str, Option<u64>)> = import.registers.iter().map(|register| (register.name.as_str(), register.offset)).collect();
+        assert_eq!(names, [("UART_CR", Some(0x4)), ("UART_SR", Some(0x8)), ("DMA_STAT", None)]);
+
+        assert_eq!(bits(&import.registers[0]), [("EN", 0, 0), ("MODE", 5, 4), ("BAUD", 15, 8)]);
+        let mode = import.registers[0].fields[1].enums.as_ref().unwrap();
+        assert_eq!(mode.iter().map(|value| (value.name.as_str(), value.value)).collect::<Vec<_>>(), [("SLOW", 1), ("FAST", 2)]);
+        assert_eq!(bits(&import.registers[1]), [("ERR", 5, 3)]);
+        assert_eq!(bits(&import.registers[2]), [("BUSY", 3, 2), ("DONE", 31, 31)]);
+    }
+
+    #[test]
+    fn reports_what_it_cant_import() {
+        assert_eq!(import().review, [
+            "uart.h:15: `UART_SR_ODD` has mask 0x5, which isn't a contiguous run of bits, left out",
+            "uart.h:16: `UART_SR_TX` has a shift of 12 but no mask or width, left out",
+            "uart.h:18: no offset macro for register `DMA_STAT`, inferred from the field names",
+        ]);
+    }
+
+    #[test]
+    fn evaluates_header_expressions() {
+        let macros = HashMap::from([("SHIFT", "4"), ("LOOP", "LOOP")]);
+        let value = |body: &str| evaluate(body, &macros, 0);
+        assert_eq!(value("(0x3UL << SHIFT) | BIT(0)"), Some(Value { number: 0x31, shifted: true }));
+        assert_eq!(value("(unsigned long)017"), Some(Value { number: 15, shifted: false }));
+        assert_eq!(value("GENMASK_ULL(63, 62)").map(|value| value.number), Some(0xc000_0000_0000_0000));
+        assert_eq!(value("LOOP"), None);
+        assert_eq!(value("\"uart\""), None);
+    }
+
+    #[test]
+    fn reports_shifts_past_the_register() {
+        let header = "\
+#define FOO_CR_A_SHIFT -1
+#define FOO_CR_A_MASK 0x1
+#define FOO_CR_B_SHIFT 0xFFFFFFFFFFFFFFF0
+#define FOO_CR_B_WIDTH 0x20
+";
+        let import = import_c_macros(&[(String::from("foo.h"), String::from(header))], &CImportOptions { size: 32, prefix: None, bit_order: BitOrder::LsbFirst });
+        assert!(import.registers.iter().all(|register| register.fields.is_empty()));
+        assert_eq!(import.review, [
+            "foo.h:1: `FOO_CR_A` doesn't fit in 64 bits with lsb 18446744073709551615 and width 1, left out",
+            "foo.h:3: `FOO_CR_B` doesn't fit in 64 bits with lsb 18446744073709551600 and width 32, left out",
+        ]);
+    }
+}
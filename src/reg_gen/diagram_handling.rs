@@ -0,0 +1,187 @@
+use std::io;
+
+use serde_json::{json, Value};
+
+use crate::reg_gen::output_handling::{escape_xml, write_generated_file};
+use crate::reg_gen::register::*;
+
+// Bits drawn per lane of a diagram, wider registers wrap onto more lanes
+const LANE_BITS: u8 = 16;
+const BIT_WIDTH: u32 = 28;
+const MARGIN: u32 = 10;
+const TITLE_HEIGHT: u32 = 24;
+const NUMBER_HEIGHT: u32 = 14;
+const BOX_HEIGHT: u32 = 36;
+const ACCESS_HEIGHT: u32 = 14;
+const LANE_GAP: u32 = 10;
+
+pub fn generate_svg(register_family: &RegisterFamily) -> Result<(), io::Error> {
+    for register in &register_family.registers {
+        write_generated_file(&format!("{}_{}.svg", register_family.register_family, register.name), &create_register_svg(register))?;
+    }
+    Ok(())
+}
+
+// WaveDrom files get a .json5 extension, which WaveDrom accepts, so they aren't mistaken for
+// register family configurations
+pub fn generate_wavedrom(register_family: &RegisterFamily) -> Result<(), io::Error> {
+    for register in &register_family.registers {
+        let wavedrom = serde_json::to_string_pretty(&create_wavedrom_reg(register)).unwrap() + "\n";
+        write_generated_file(&format!("{}_{}.json5", register_family.register_family, register.name), &wavedrom)?;
+    }
+    Ok(())
+}
+
+// A WaveDrom "reg" description, which lists fields from the least significant bit up with
+// unnamed entries for unused bits
+pub fn create_wavedrom_reg(register: &Register) -> Value {
+    let mut segments = if register.size == 0 { Vec::new() } else { register.bit_segments(register.size - 1, 0) };
+    segments.reverse();
+
+    let reg: Vec<Value> = segments
+        .iter()
+        .map(|segment| {
+            let bits = segment.msb - segment.lsb + 1;
+            match segment.field {
+                Some(field) => {
+                    let mut attributes = vec![field.access().to_string()];
+                    if field.negative.unwrap_or(false) {
+                        attributes.push(String::from("signed"));
+                    }
+                    json!({ "bits": bits, "name": field.name, "attr": attributes, "type": access_type(field) })
+                },
+                None => json!({ "bits": bits }),
+            }
+        })
+        .collect();
+
+    let lanes = (register.size / LANE_BITS).max(1);
+    json!({ "reg": reg, "config": { "bits": register.size, "lanes": lanes } })
+}
+
+// WaveDrom colours fields by type, give each kind of access its own colour
+fn access_type(field: &Field) -> u8 {
+    match (field.read, field.write) {
+        (true, true) => 2,
+        (true, false) => 3,
+        (false, true) => 4,
+        (false, false) => 5,
+    }
+}
+
+// Draws the register as rows of bit boxes, most significant bit first like a datasheet. Each
+// field shows its name in the box, its bit range above and its access below.
+pub fn create_register_svg(register: &Register) -> String {
+    let lane_bits = LANE_BITS.min(register.size).max(1);
+    let lanes = (register.size / lane_bits).max(1) as u32;
+    let lane_height = NUMBER_HEIGHT + BOX_HEIGHT + ACCESS_HEIGHT + LANE_GAP;
+    let width = 2 * MARGIN + lane_bits as u32 * BIT_WIDTH;
+    let height = 2 * MARGIN + TITLE_HEIGHT + lanes * lane_height;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\">\n\
+        <rect width=\"{0}\" height=\"{1}\" fill=\"white\"/>\n\
+        <text x=\"{2}\" y=\"{3}\" font-size=\"14\" font-weight=\"bold\">{4} ({5}-bit)</text>\n",
+        width, height, MARGIN, MARGIN + 14, escape_xml(&register.name), register.size
+    );
+
+    for lane in 0..lanes {
+        let lane_msb = register.size.saturating_sub(1) - (lane as u8 * lane_bits);
+        let lane_lsb = lane_msb + 1 - lane_bits;
+        let top = MARGIN + TITLE_HEIGHT + lane * lane_height;
+        let box_top = top + NUMBER_HEIGHT;
+
+        for segment in register.bit_segments(lane_msb, lane_lsb) {
+            let x = MARGIN + (lane_msb - segment.msb) as u32 * BIT_WIDTH;
+            let box_width = (segment.msb - segment.lsb + 1) as u32 * BIT_WIDTH;
+            let center = x + box_width / 2;
+
+            // Bit numbers at both ends of the segment
+            svg.push_str(&format!("<text x=\"{}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\">{}</text>\n", x + BIT_WIDTH / 2, top + 10, segment.msb));
+            if segment.msb != segment.lsb {
+                svg.push_str(&format!("<text x=\"{}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\">{}</text>\n", x + box_width - BIT_WIDTH / 2, top + 10, segment.lsb));
+            }
+
+            match segment.field {
+                Some(field) => {
+                    let font_size = fitting_font_size(&field.name, box_width);
+                    svg.push_str(&format!(
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#dbe9f7\" stroke=\"black\"/>\n\
+                        <text x=\"{}\" y=\"{}\" font-size=\"{:.1}\" text-anchor=\"middle\" dominant-baseline=\"middle\">{}</text>\n",
+                        x, box_top, box_width, BOX_HEIGHT, center, box_top + BOX_HEIGHT / 2, font_size, escape_xml(&field.name)
+                    ));
+                    let mut access = field.access().to_string();
+                    if field.negative.unwrap_or(false) {
+                        access.push_str(" signed");
+                    }
+                    svg.push_str(&format!(
+                        "<text x=\"{}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\" fill=\"#555\">{}</text>\n",
+                        center, box_top + BOX_HEIGHT + 11, access
+                    ));
+                },
+                None => svg.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#dddddd\" stroke=\"black\"/>\n",
+                    x, box_top, box_width, BOX_HEIGHT
+                )),
+            }
+        }
+
+        // Tick marks between every bit
+        for bit in 1..lane_bits as u32 {
+            let x = MARGIN + bit * BIT_WIDTH;
+            svg.push_str(&format!(
+                "<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"black\" stroke-width=\"0.5\"/>\n",
+                x, box_top + BOX_HEIGHT - 6, box_top + BOX_HEIGHT
+            ));
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Shrinks long names so they stay inside their box
+fn fitting_font_size(text: &str, box_width: u32) -> f32 {
+    let characters = text.chars().count().max(1) as f32;
+    ((box_width as f32 - 4.0) / (characters * 0.6)).clamp(6.0, 12.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register() -> Register {
+        Register {
+            name: String::from("CTRL"),
+            size: 32,
+            fields: vec![
+                Field { name: String::from("en"), lsb: 0, msb: 0, read: true, write: true, ..Default::default() },
+                Field { name: String::from("level"), lsb: 4, msb: 19, read: true, negative: Some(true), ..Default::default() },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn describes_fields_from_the_lsb_for_wavedrom() {
+        assert_eq!(create_wavedrom_reg(&register()), json!({
+            "reg": [
+                { "bits": 1, "name": "en", "attr": ["RW"], "type": 2 },
+                { "bits": 3 },
+                { "bits": 16, "name": "level", "attr": ["RO", "signed"], "type": 3 },
+                { "bits": 12 },
+            ],
+            "config": { "bits": 32, "lanes": 2 },
+        }));
+    }
+
+    #[test]
+    fn draws_fields_split_across_lanes() {
+        let svg = create_register_svg(&register());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"468\" height=\"192\""));
+        // level crosses from the upper lane into the lower one
+        assert_eq!(svg.matches(">level</text>").count(), 2);
+        assert_eq!(svg.matches(">RO signed</text>").count(), 2);
+        assert_eq!(svg.matches(">en</text>").count(), 1);
+    }
+}
@@ -0,0 +1,262 @@
+use serde::Serialize;
+
+use crate::reg_gen::register::*;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Compatibility {
+    // Code written against the old headers may no longer compile
+    Breaking,
+    Compatible,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Change {
+    pub compatibility: Compatibility,
+    // Family, register or register.field the change applies to
+    pub location: String,
+    pub description: String,
+}
+
+// Compares two versions of a register family. Registers and fields are matched by name, and an
+// unmatched removal and addition that look like the same register or field are reported as a
+// rename followed by whatever else changed.
+pub fn diff_register_families(old: &RegisterFamily, new: &RegisterFamily) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    if old.register_family != new.register_family {
+        changes.push(Change {
+            compatibility: Compatibility::Breaking,
+            location: old.register_family.clone(),
+            description: format!("family renamed to {}, which renames the generated header", new.register_family),
+        });
+    }
+    for width in &old.register_family_widths {
+        if !new.register_family_widths.contains(width) {
+            changes.push(family_change(new, Compatibility::Breaking, format!("{}-bit base register removed", width)));
+        }
+    }
+    for width in &new.register_family_widths {
+        if !old.register_family_widths.contains(width) {
+            changes.push(family_change(new, Compatibility::Compatible, format!("{}-bit base register added", width)));
+        }
+    }
+    if old.base_address != new.base_address {
+        changes.push(family_change(new, Compatibility::Compatible, format!("base address changed from {} to {}", format_address(old.base_address), format_address(new.base_address))));
+    }
+
+    let (matched, removed, added) = match_by_name(&old.registers, &new.registers, |register| &register.name);
+    for (old_register, new_register) in matched {
+        diff_registers(old_register, new_register, &new_register.name, &mut changes);
+    }
+
+    let mut added: Vec<&Register> = added;
+    for old_register in removed {
+        let renamed = added.iter().position(|new_register| likely_same_register(old_register, new_register));
+        match renamed {
+            Some(index) => {
+                let new_register = added.remove(index);
+                changes.push(Change {
+                    compatibility: Compatibility::Breaking,
+                    location: old_register.name.clone(),
+                    description: format!("register renamed to {}", new_register.name),
+                });
+                diff_registers(old_register, new_register, &new_register.name, &mut changes);
+            },
+            None => changes.push(Change {
+                compatibility: Compatibility::Breaking,
+                location: old_register.name.clone(),
+                description: String::from("register removed"),
+            }),
+        }
+    }
+    for new_register in added {
+        changes.push(Change {
+            compatibility: Compatibility::Compatible,
+            location: new_register.name.clone(),
+            description: format!("{}-bit register added", new_register.size),
+        });
+    }
+
+    changes
+}
+
+fn diff_registers(old: &Register, new: &Register, name: &str, changes: &mut Vec<Change>) {
+    if old.size != new.size {
+        changes.push(Change {
+            compatibility: Compatibility::Breaking,
+            location: name.to_string(),
+            description: format!("width changed from {} to {} bits", old.size, new.size),
+        });
+    }
+    if old.offset != new.offset {
+        changes.push(Change {
+            compatibility: Compatibility::Compatible,
+            location: name.to_string(),
+            description: format!("offset changed from {} to {}", format_address(old.offset), format_address(new.offset)),
+        });
+    }
+
+    let (matched, removed, added) = match_by_name(&old.fields, &new.fields, |field| &field.name);
+    for (old_field, new_field) in matched {
+        diff_fields(old_field, new_field, name, changes);
+    }
+
+    let mut added: Vec<&Field> = added;
+    for old_field in removed {
+        let location = format!("{}.{}", name, old_field.name);
+        // A field at exactly the same bits is taken to be the same field
+        let renamed = added.iter().position(|new_field| new_field.lsb == old_field.lsb && new_field.msb == old_field.msb);
+        match renamed {
+            Some(index) => {
+                let new_field = added.remove(index);
+                changes.push(Change {
+                    compatibility: breaking_if(has_methods(old_field)),
+                    location,
+                    description: format!("field renamed to {}", new_field.name),
+                });
+                diff_fields(old_field, new_field, name, changes);
+            },
+            None => changes.push(Change {
+                compatibility: breaking_if(has_methods(old_field)),
+                location,
+                description: String::from("field removed"),
+            }),
+        }
+    }
+    for new_field in added {
+        changes.push(Change {
+            compatibility: Compatibility::Compatible,
+            location: format!("{}.{}", name, new_field.name),
+            description: format!("field added at bits {}", bit_range(new_field)),
+        });
+    }
+}
+
+fn diff_fields(old: &Field, new: &Field, register_name: &str, changes: &mut Vec<Change>) {
+    let location = format!("{}.{}", register_name, new.name);
+    let mut push = |compatibility, description: String| changes.push(Change { compatibility, location: location.clone(), description });
+
+    // Moving or resizing a field keeps the method signatures, so existing code still compiles
+    if old.lsb != new.lsb || old.msb != new.msb {
+        push(Compatibility::Compatible, format!("bits changed from {} to {}", bit_range(old), bit_range(new)));
+    }
+    if old.read != new.read {
+        let description = if new.read { "get method added" } else { "get method removed" };
+        push(breaking_if(old.read), description.to_string());
+    }
+    if old.write != new.write {
+        let description = if new.write { "set method added" } else { "set method removed" };
+        push(breaking_if(old.write), description.to_string());
+    }
+    // The get and set methods switch between signed and unsigned types
+    let old_negative = old.negative.unwrap_or(false);
+    let new_negative = new.negative.unwrap_or(false);
+    if old_negative != new_negative && (old.read || old.write) {
+        let description = if new_negative { "changed from unsigned to signed" } else { "changed from signed to unsigned" };
+        push(Compatibility::Breaking, description.to_string());
+    }
+}
+
+// Splits two lists into pairs with the same name, entries only in the old list and entries only
+// in the new list, keeping the original order
+fn match_by_name<'a, T>(old: &'a [T], new: &'a [T], name: impl Fn(&T) -> &String) -> (Vec<(&'a T, &'a T)>, Vec<&'a T>, Vec<&'a T>) {
+    let mut matched = Vec::new();
+    let mut removed = Vec::new();
+    for old_item in old {
+        match new.iter().find(|new_item| name(new_item) == name(old_item)) {
+            Some(new_item) => matched.push((old_item, new_item)),
+            None => removed.push(old_item),
+        }
+    }
+    let added = new
+        .iter()
+        .filter(|new_item| !old.iter().any(|old_item| name(old_item) == name(new_item)))
+        .collect();
+    (matched, removed, added)
+}
+
+// Registers at the same address are the same register. Without addresses, a register of the same
+// width where most of the old fields are still at the same bits is taken to be the same register.
+fn likely_same_register(old: &Register, new: &Register) -> bool {
+    if let (Some(old_offset), Some(new_offset)) = (old.offset, new.offset) {
+        return old_offset == new_offset;
+    }
+    let kept_fields = old.fields
+        .iter()
+        .filter(|old_field| new.fields.iter().any(|new_field| new_field.lsb == old_field.lsb && new_field.msb == old_field.msb))
+        .count();
+    old.size == new.size && kept_fields * 2 > old.fields.len()
+}
+
+fn has_methods(field: &Field) -> bool {
+    field.read || field.write
+}
+
+fn breaking_if(breaking: bool) -> Compatibility {
+    if breaking {
+        Compatibility::Breaking
+    } else {
+        Compatibility::Compatible
+    }
+}
+
+fn family_change(family: &RegisterFamily, compatibility: Compatibility, description: String) -> Change {
+    Change { compatibility, location: family.register_family.clone(), description }
+}
+
+fn bit_range(field: &Field) -> String {
+    format!("{}:{}", field.msb, field.lsb)
+}
+
+fn format_address(address: Option<u64>) -> String {
+    match address {
+        Some(address) => format!("{:#x}", address),
+        None => String::from("none"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, lsb: u8, msb: u8) -> Field {
+        Field { name: name.to_string(), lsb, msb, read: true, write: true, ..Default::default() }
+    }
+
+    fn family(registers: Vec<Register>) -> RegisterFamily {
+        RegisterFamily { register_family: String::from("Test"), register_family_widths: vec![32], registers, ..Default::default() }
+    }
+
+    fn summary(changes: &[Change]) -> Vec<(Compatibility, &str, &str)> {
+        changes.iter().map(|change| (change.compatibility, change.location.as_str(), change.description.as_str())).collect()
+    }
+
+    #[test]
+    fn detects_renamed_registers_and_fields() {
+        let old = family(vec![Register { name: String::from("CTRL"), size: 32, fields: vec![field("en", 0, 0), field("mode", 1, 3), field("irq", 8, 8)], ..Default::default() }]);
+        let new = family(vec![Register { name: String::from("CONTROL"), size: 32, fields: vec![field("enable", 0, 0), field("mode", 1, 4), field("irq", 8, 8)], ..Default::default() }]);
+
+        assert_eq!(summary(&diff_register_families(&old, &new)), vec![
+            (Compatibility::Breaking, "CTRL", "register renamed to CONTROL"),
+            (Compatibility::Compatible, "CONTROL.mode", "bits changed from 3:1 to 4:1"),
+            (Compatibility::Breaking, "CONTROL.en", "field renamed to enable"),
+        ]);
+    }
+
+    #[test]
+    fn classifies_access_changes() {
+        let mut read_only = field("status", 0, 7);
+        read_only.write = false;
+        let mut negative = field("level", 8, 15);
+        negative.negative = Some(true);
+        let old = family(vec![Register { name: String::from("STAT"), size: 32, fields: vec![field("status", 0, 7), field("level", 8, 15)], ..Default::default() }]);
+        let new = family(vec![Register { name: String::from("STAT"), size: 32, fields: vec![read_only, negative, field("extra", 16, 16)], ..Default::default() }]);
+
+        assert_eq!(summary(&diff_register_families(&old, &new)), vec![
+            (Compatibility::Breaking, "STAT.status", "set method removed"),
+            (Compatibility::Breaking, "STAT.level", "changed from unsigned to signed"),
+            (Compatibility::Compatible, "STAT.extra", "field added at bits 16:16"),
+        ]);
+    }
+}
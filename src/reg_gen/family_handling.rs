@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use crate::reg_gen::register::*;
+
+// Combines families into one named after the first. Offsets are moved to be relative to the first
+// family's base address. Returns every conflict found rather than stopping at the first.
+pub fn merge_register_families(families: Vec<RegisterFamily>) -> Result<RegisterFamily, Vec<String>> {
+    let mut families = families.into_iter();
+    let Some(mut merged) = families.next() else {
+        return Err(vec![String::from("Nothing to merge")]);
+    };
+
+    // Which family each register came from, for reporting conflicts
+    let mut origins: HashMap<String, String> = merged.registers
+        .iter()
+        .map(|register| (register.name.clone(), merged.register_family.clone()))
+        .collect();
+
+    let mut problems = Vec::new();
+    for family in families {
+        for width in &family.register_family_widths {
+            if !merged.register_family_widths.contains(width) {
+                merged.register_family_widths.push(*width);
+            }
+        }
+
+        let base_address = family.base_address;
+        for mut register in family.registers {
+            if let Some(origin) = origins.get(&register.name) {
+                problems.push(format!("Register `{}` from {} conflicts with the one from {}", register.name, family.register_family, origin));
+                continue;
+            }
+
+            if let Some(offset) = register.offset {
+                let address = base_address.unwrap_or(0) as i128 + offset as i128;
+                let rebased = address - merged.base_address.unwrap_or(0) as i128;
+                match u64::try_from(rebased) {
+                    Ok(rebased) => register.offset = Some(rebased),
+                    Err(_) => {
+                        problems.push(format!(
+                            "Register `{}` from {} is below the base address of {}",
+                            register.name, family.register_family, merged.register_family
+                        ));
+                        continue;
+                    },
+                }
+            }
+            origins.insert(register.name.clone(), family.register_family.clone());
+            merged.registers.push(register);
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(merged)
+    } else {
+        Err(problems)
+    }
+}
+
+// Moves the named registers out of the family into a new one with the same base address and only
+// the widths the moved registers need
+pub fn split_register_family(register_family: &mut RegisterFamily, names: &[String], new_name: String) -> Result<RegisterFamily, Vec<String>> {
+    let missing: Vec<String> = names
+        .iter()
+        .filter(|name| !register_family.registers.iter().any(|register| &&register.name == name))
+        .map(|name| format!("No register named `{}` in {}", name, register_family.register_family))
+        .collect();
+    if !missing.is_empty() {
+        return Err(missing);
+    }
+
+    let (moved, kept): (Vec<Register>, Vec<Register>) = register_family.registers
+        .drain(..)
+        .partition(|register| names.contains(&register.name));
+    register_family.registers = kept;
+
+    let register_family_widths = register_family.register_family_widths
+        .iter()
+        .filter(|width| moved.iter().any(|register| &register.size == *width))
+        .cloned()
+        .collect();
+
+    Ok(RegisterFamily {
+        register_family: new_name,
+        register_family_widths,
+        registers: moved,
+        base_address: register_family.base_address,
+        schema: register_family.schema.clone(),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(name: &str, size: u8, offset: Option<u64>) -> Register {
+        Register { name: name.to_string(), size, offset, ..Default::default() }
+    }
+
+    fn family(name: &str, widths: Vec<u8>, registers: Vec<Register>, base_address: Option<u64>) -> RegisterFamily {
+        RegisterFamily { register_family: name.to_string(), register_family_widths: widths, registers, base_address, ..Default::default() }
+    }
+
+    #[test]
+    fn merges_widths_and_rebases_offsets() {
+        let a = family("A", vec![32], vec![register("CTRL", 32, Some(0x4))], Some(0x1000));
+        let b = family("B", vec![8, 32], vec![register("IRQ", 8, Some(0x10))], Some(0x1100));
+
+        let merged = merge_register_families(vec![a, b]).unwrap();
+        assert_eq!(merged.register_family, "A");
+        assert_eq!(merged.register_family_widths, vec![32, 8]);
+        assert_eq!(merged.registers[1].offset, Some(0x110));
+    }
+
+    #[test]
+    fn reports_conflicting_register_names() {
+        let a = family("A", vec![32], vec![register("CTRL", 32, None)], None);
+        let b = family("B", vec![32], vec![register("STAT", 32, None)], None);
+        let c = family("C", vec![32], vec![register("STAT", 32, None), register("CTRL", 32, None)], None);
+
+        assert_eq!(merge_register_families(vec![a, b, c]).unwrap_err(), vec![
+            String::from("Register `STAT` from C conflicts with the one from B"),
+            String::from("Register `CTRL` from C conflicts with the one from A"),
+        ]);
+    }
+
+    #[test]
+    fn splits_named_registers_into_a_new_family() {
+        let mut a = family("A", vec![8, 32], vec![register("CTRL", 32, None), register("IRQ", 8, None)], Some(0x1000));
+
+        let b = split_register_family(&mut a, &[String::from("IRQ")], String::from("B")).unwrap();
+        assert_eq!(a.registers.len(), 1);
+        assert_eq!(b.register_family_widths, vec![8]);
+        assert_eq!(b.registers[0].name, "IRQ");
+        assert_eq!(b.base_address, Some(0x1000));
+    }
+}
@@ -0,0 +1,221 @@
+use std::{path::Path, sync::{atomic::{AtomicBool, Ordering}, OnceLock}};
+
+use clap::ValueEnum;
+use serde_json::Value;
+
+use crate::reg_gen::register::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+// Set once from the global --format flag, after which it is used for every file instead of the
+// file extension
+static FORMAT_OVERRIDE: OnceLock<ConfigFormat> = OnceLock::new();
+
+pub fn set_format_override(format: ConfigFormat) {
+    FORMAT_OVERRIDE.set(format).expect("The configuration format was already set");
+}
+
+pub fn format_override() -> Option<ConfigFormat> {
+    FORMAT_OVERRIDE.get().copied()
+}
+
+// Set from the global --discard-comments flag, allowing YAML and TOML files with comments to be
+// rewritten
+static DISCARD_COMMENTS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_discard_comments() {
+    DISCARD_COMMENTS.store(true, Ordering::Relaxed);
+}
+
+pub fn discard_comments() -> bool {
+    DISCARD_COMMENTS.load(Ordering::Relaxed)
+}
+
+impl ConfigFormat {
+    pub fn from_extension(path: &str) -> Option<ConfigFormat> {
+        match Path::new(path).extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "toml" => Some(ConfigFormat::Toml),
+            _ => None,
+        }
+    }
+
+    // The format of a configuration file, falling back to JSON for unknown extensions
+    pub fn of_path(path: &str) -> ConfigFormat {
+        format_override()
+            .or_else(|| ConfigFormat::from_extension(path))
+            .unwrap_or(ConfigFormat::Json)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Toml => "TOML",
+        }
+    }
+
+    // Every format is read into a JSON value first, so the same schema checks apply to all of them
+    pub fn parse(self, contents: &str) -> Result<Value, String> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|why| why.to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|why| why.to_string()),
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|why| why.to_string()),
+        }
+    }
+
+    // Whether the text has comments, which are lost when a configuration is rewritten through serde
+    pub fn has_comments(self, contents: &str) -> bool {
+        match self {
+            ConfigFormat::Json => false,
+            ConfigFormat::Yaml => yaml_has_comments(contents),
+            ConfigFormat::Toml => toml_has_comments(contents),
+        }
+    }
+
+    pub fn serialize(self, register_family: &RegisterFamily) -> Result<String, String> {
+        match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(register_family).map(|json| json + "\n").map_err(|why| why.to_string()),
+            ConfigFormat::Yaml => serde_yaml::to_string(register_family).map_err(|why| why.to_string()),
+            ConfigFormat::Toml => toml::to_string_pretty(register_family).map_err(|why| why.to_string()),
+        }
+    }
+}
+
+// A # starts a comment anywhere outside of a string
+fn toml_has_comments(contents: &str) -> bool {
+    let mut rest = contents;
+    while let Some(c) = rest.chars().next() {
+        let (quote, escapes) = match c {
+            '#' => return true,
+            '"' => ("\"", true),
+            '\'' => ("'", false),
+            _ => {
+                rest = &rest[c.len_utf8()..];
+                continue;
+            },
+        };
+        // Multi-line strings are delimited by three quotes
+        let quote = if rest.starts_with(&quote.repeat(3)) { quote.repeat(3) } else { quote.to_string() };
+        rest = &rest[quote.len()..];
+        loop {
+            if escapes && rest.starts_with('\\') {
+                rest = rest.get(2..).unwrap_or_default();
+            } else if rest.starts_with(&quote) {
+                rest = &rest[quote.len()..];
+                break;
+            } else if let Some(c) = rest.chars().next() {
+                rest = &rest[c.len_utf8()..];
+            } else {
+                return false;
+            }
+        }
+    }
+    false
+}
+
+// A # starts a comment at the start of a line or after whitespace, outside of quoted scalars and
+// the indented lines of | and > block scalars
+fn yaml_has_comments(contents: &str) -> bool {
+    let mut block_scalar_indent: Option<usize> = None;
+    for line in contents.lines() {
+        let content = line.trim_start();
+        let indent = line.len() - content.len();
+        match block_scalar_indent {
+            Some(parent) if content.is_empty() || indent > parent => continue,
+            _ => block_scalar_indent = None,
+        }
+
+        let mut quote: Option<char> = None;
+        let mut previous = ' ';
+        let mut value = String::new();
+        for c in content.chars() {
+            match quote {
+                Some(open) if c == open => quote = None,
+                Some(_) => (),
+                // Quotes only open a scalar where one can start, not in the middle of words
+                None if (c == '"' || c == '\'') && matches!(value.trim_end().chars().last(), None | Some(':' | '-' | ',' | '[' | '{' | '?')) => quote = Some(c),
+                None if c == '#' && previous.is_whitespace() => return true,
+                None => (),
+            }
+            value.push(c);
+            previous = c;
+        }
+
+        let value = value.trim_end().trim_end_matches(['+', '-', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9']);
+        if quote.is_none() && (value.ends_with(" |") || value.ends_with(" >") || value == "|" || value == ">") {
+            block_scalar_indent = Some(indent);
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_format_round_trips() {
+        let register_family = RegisterFamily {
+            register_family: String::from("Test"),
+            register_family_widths: vec![32],
+            registers: vec![Register {
+                name: String::from("CTRL"),
+                size: 32,
+                offset: Some(0x10),
+                description: Some(String::from("Control")),
+                long_description: Some(String::from("Enables the block.\n\nTakes effect immediately.")),
+                fields: vec![
+                    Field { name: String::from("en"), lsb: 0, msb: 0, read: true, write: true, ..Default::default() },
+                    Field { name: String::from("level"), lsb: 1, msb: 4, read: true, negative: Some(true), enums: Some(vec![EnumValue { name: String::from("LOW"), value: -8 }]), ..Default::default() },
+                ],
+            }],
+            base_address: Some(0x4000_0000),
+            ..Default::default()
+        };
+        let json = ConfigFormat::Json.serialize(&register_family).unwrap();
+
+        for format in [ConfigFormat::Yaml, ConfigFormat::Toml] {
+            let text = format.serialize(&register_family).unwrap();
+            let parsed: RegisterFamily = serde_json::from_value(format.parse(&text).unwrap()).unwrap();
+            assert_eq!(ConfigFormat::Json.serialize(&parsed).unwrap(), json, "{} didn't round trip", format.name());
+        }
+    }
+
+    #[test]
+    fn finds_comments_outside_strings() {
+        assert!(ConfigFormat::Toml.has_comments("# Registers\nregister_family = \"Hif\"\n"));
+        assert!(ConfigFormat::Toml.has_comments("size = 32 # bits\n"));
+        assert!(!ConfigFormat::Toml.has_comments("description = \"Bit #3\"\nname = 'a#b'\nlong_description = \"\"\"\nSee \\\"#4\\\"\n# not a comment\"\"\"\n"));
+
+        assert!(ConfigFormat::Yaml.has_comments("# Registers\nregister_family: Hif\n"));
+        assert!(ConfigFormat::Yaml.has_comments("registers:\n- name: CTRL  # control\n"));
+        assert!(!ConfigFormat::Yaml.has_comments("description: Bit#3\nname: 'a #b'\nother: \"it's # fine\"\n"));
+        assert!(!ConfigFormat::Yaml.has_comments("long_description: |-\n  First\n\n  # not a comment\nname: don't\n"));
+        assert!(ConfigFormat::Yaml.has_comments("long_description: >\n  First\n# comment\n"));
+
+        // Nothing the serialisers write looks like a comment
+        let register_family = RegisterFamily {
+            register_family: String::from("Test"),
+            description: Some(String::from("Channel #1's \"control\" registers")),
+            long_description: Some(String::from("# Heading\n\nSee 'section #2'.")),
+            ..Default::default()
+        };
+        for format in [ConfigFormat::Yaml, ConfigFormat::Toml] {
+            assert!(!format.has_comments(&format.serialize(&register_family).unwrap()), "{}", format.name());
+        }
+    }
+
+    #[test]
+    fn detects_formats_from_extensions() {
+        assert_eq!(ConfigFormat::from_extension("a/b.YML"), Some(ConfigFormat::Yaml));
+        assert_eq!(ConfigFormat::from_extension("b.toml"), Some(ConfigFormat::Toml));
+        assert_eq!(ConfigFormat::from_extension("b.txt"), None);
+    }
+}
@@ -1,22 +1,31 @@
 use std::{path::Path, fs::{File, OpenOptions}, io::Write, collections::HashSet};
 use crate::reg_gen::register::*;
+use crate::reg_gen::output_handling::identifier;
 
-pub fn generate_files(register_family: &RegisterFamily) {
-    create_base_register_files(&register_family.register_family_widths, &register_family.register_family);
+pub fn generate_files(register_family: &RegisterFamily, mmio: bool) {
+    create_base_register_files(register_family, mmio);
 
     for register in &register_family.registers {
-        write_register_to_file(&register, &register_family.register_family);
+        write_register_to_file(register, &register_family.register_family, mmio);
+    }
+
+    if mmio {
+        if let Some(peripheral) = create_peripheral_struct(register_family) {
+            append_to_family_file(&register_family.register_family, &peripheral);
+        }
     }
 }
 
-fn create_base_register_files(register_widths: &Vec<u8>, register_family: &String) {
+fn create_base_register_files(family: &RegisterFamily, mmio: bool) {
+    let register_widths = &family.register_family_widths;
+    let register_family = &family.register_family;
     for register_width in register_widths {
         // Create the base register file
         let file_name = format!("Register{}.h", register_width);
         let path = Path::new(&file_name);
         let display = path.display();
 
-        let mut file = match File::create(&path) {
+        let mut file = match File::create(path) {
             Err(why) => panic!("Couldn't create {}: {}", display, why),
             Ok(file) => file,
         };
@@ -33,6 +42,9 @@ fn create_base_register_files(register_widths: &Vec<u8>, register_family: &Strin
             class Register{0} {{\n\
             public:\n\
             \tRegister{0}() = default;\n\
+            \tusing raw_type = uint{0}_t;\n\
+            \tinline void read_from(const volatile uint{0}_t *address) {{ register_raw = *address; }};\n\
+            \tinline void write_to(volatile uint{0}_t *address) const {{ *address = register_raw; }};\n\
             \tinline uint{0}_t get_register_value() const {{ return register_raw; }};\n\
             \tinline void clear_register_value() {{ register_raw = 0x0; }};\n\
             \tinline void set_register_value(uint{0}_t value) {{ register_raw = value; }};\n\
@@ -48,7 +60,7 @@ fn create_base_register_files(register_widths: &Vec<u8>, register_family: &Strin
             protected:\n\
             \tuint{0}_t register_raw = 0x0;\n\
             }};\n",
-           register_width 
+           register_width
         );
 
         match file.write_all(full_string.as_bytes()) {
@@ -63,7 +75,7 @@ fn create_base_register_files(register_widths: &Vec<u8>, register_family: &Strin
 
     let display = path.display();
 
-    let mut file = match File::create(&path) {
+    let mut file = match File::create(path) {
         Err(why) => panic!("Couldn't create {}: {}", display, why),
         Ok(file) => file,
     };
@@ -73,18 +85,24 @@ fn create_base_register_files(register_widths: &Vec<u8>, register_family: &Strin
         let include = format!("#include \"Register{}.h\"\n", register_width);
         includes.push_str(&include);
     }
+    if mmio && family.base_address.is_some() {
+        create_mmio_register_file();
+        includes.push_str("#include \"MmioRegister.h\"\n");
+    }
 
     let full_string = format!(
         "// This file was automatically generated by a register generation tool\n\
         // https://github.com/regerj/register_generator\n\
         // Any changes to this file may be overwritten on subsequent generations\n\
         \n\
+        {1}\
         #pragma once\n\
         \n\
         #include <cstdint>\n\
         \n\
         {0}\n",
-        includes
+        includes,
+        create_file_comment(family)
     );
 
     match file.write_all(full_string.as_bytes()) {
@@ -93,24 +111,76 @@ fn create_base_register_files(register_widths: &Vec<u8>, register_family: &Strin
     }
 }
 
-fn write_register_to_file(register: &Register, register_family: &String) {
+// The typed handle each member of a family's peripheral struct is made of, shared by every family
+fn create_mmio_register_file() {
+    let file_name = "MmioRegister.h";
+    let path = Path::new(file_name);
+    let display = path.display();
+
+    let mut file = match File::create(path) {
+        Err(why) => panic!("Couldn't create {}: {}", display, why),
+        Ok(file) => file,
+    };
+
+    let full_string = "\
+        // This file was automatically generated by a register generation tool\n\
+        // https://github.com/regerj/register_generator\n\
+        // Any changes to this file may be overwritten on subsequent generations\n\
+        \n\
+        #pragma once\n\
+        \n\
+        #include <cstdint>\n\
+        \n\
+        /// A register class bound to its hardware address\n\
+        template <typename RegisterType>\n\
+        class MmioRegister {\n\
+        public:\n\
+        \texplicit MmioRegister(uintptr_t location) : address(reinterpret_cast<volatile typename RegisterType::raw_type *>(location)) {};\n\
+        \tinline RegisterType read() const { RegisterType reg; reg.read_from(address); return reg; };\n\
+        \tinline void write(const RegisterType &reg) const { reg.write_to(address); };\n\
+        \ttemplate <typename Modifier>\n\
+        \tinline void modify(Modifier modifier) const { RegisterType reg; reg.modify(address, modifier); };\n\
+        \tinline volatile typename RegisterType::raw_type *get_address() const { return address; };\n\
+        private:\n\
+        \tvolatile typename RegisterType::raw_type *address;\n\
+        };\n";
+
+    match file.write_all(full_string.as_bytes()) {
+        Err(why) => panic!("Couldn't write to {}: {}", display, why),
+        Ok(_) => println!("Wrote {}", display),
+    }
+}
+
+fn write_register_to_file(register: &Register, register_family: &String, mmio: bool) {
     // Supported register widths
     let supported_register_widths: HashSet<u8> = HashSet::from([8, 16, 32, 64]);
     if !supported_register_widths.contains(&register.size) {
         panic!("Invalid register width!");
     } 
 
+    append_to_family_file(register_family, &create_register_class(register, mmio));
+}
+
+fn append_to_family_file(register_family: &String, full_string: &str) {
     // Determine the name of the register file and get the path
     let file_name = format!("{}Registers.h", register_family);
     let path = Path::new(&file_name);
     let display = path.display();
 
     // Open for appending
-    let mut file = match OpenOptions::new().append(true).create(false).open(&path) {
+    let mut file = match OpenOptions::new().append(true).create(false).open(path) {
         Err(why) => panic!("Couldn't create {}: {}", display, why),
         Ok(file) => file,
     };
 
+    match file.write_all(full_string.as_bytes()) {
+        Err(why) => panic!("Couldn't write to {}: {}", display, why),
+        Ok(_) => println!("Wrote {}", display),
+    }
+}
+
+// Builds the class definition for a single register, exactly as it is written to the family header
+pub fn create_register_class(register: &Register, mmio: bool) -> String {
     // Strings for each set of methods
     let mut get_methods = String::new();
     let mut set_methods = String::new();
@@ -118,16 +188,36 @@ fn write_register_to_file(register: &Register, register_family: &String) {
     // Check permissions and create a method if allowed
     for current_field in register.fields.iter() {
         if current_field.read {
+            get_methods.push_str(create_field_comment(current_field, None).as_str());
             get_methods.push_str(current_field.create_get_method(register.size).as_str());
         }
 
         if current_field.write {
+            set_methods.push_str(create_field_comment(current_field, Some("@return false if the value doesn't fit in the field, leaving the register unchanged")).as_str());
             set_methods.push_str(current_field.create_set_method(register.size).as_str());
         }
     }
 
-    let full_string = format!(
-        "class {0} : public Register{3} {{\n\
+    let modify_method = if mmio {
+        format!(
+            "\n\t// Read-modify-write\n\
+            \t/// Reads the register at address, passes it to modifier to change and writes it back\n\
+            \ttemplate <typename Modifier>\n\
+            \tinline void modify(volatile uint{0}_t *address, Modifier modifier) {{ read_from(address); modifier(*this); write_to(address); }};\n",
+            register.size
+        )
+    } else {
+        String::new()
+    };
+
+    let mut details = format!("{}-bit register", register.size);
+    if let Some(offset) = register.offset {
+        details.push_str(&format!(" at offset {:#x}", offset));
+    }
+
+    format!(
+        "{4}\
+        class {0} : public Register{3} {{\n\
         public:\n\
         \t{0}() : Register{3}() {{}};\n\
         \n\
@@ -135,15 +225,149 @@ fn write_register_to_file(register: &Register, register_family: &String) {
         {1}\n\
         \t// Set methods\n\
         {2}\
+        {5}\
         }};\n\n",
         register.name,
         get_methods,
         set_methods,
-        register.size
-    );
+        register.size,
+        create_doxygen_comment("", &register.description, &register.long_description, &[details]),
+        modify_method
+    )
+}
 
-    match file.write_all(full_string.as_bytes()) {
-        Err(why) => panic!("Couldn't write to {}: {}", display, why),
-        Ok(_) => println!("Wrote {}", display),
+// Maps each register with an offset to its address from the family's base address, which can be
+// moved when constructing, e.g. to a second instance of the peripheral. Members are named after
+// their register, so the types are written with the global scope to tell them apart.
+pub fn create_peripheral_struct(family: &RegisterFamily) -> Option<String> {
+    let base_address = family.base_address?;
+    let mapped: Vec<&Register> = family.registers.iter().filter(|register| register.offset.is_some()).collect();
+    if mapped.is_empty() {
+        return None;
+    }
+
+    let initializers: Vec<String> = mapped
+        .iter()
+        .map(|register| format!("{}(base + {:#x})", register.name, register.offset.unwrap_or_default()))
+        .collect();
+    let mut members = String::new();
+    for register in &family.registers {
+        match register.offset {
+            Some(_) => members.push_str(&format!("\tMmioRegister<::{0}> {0};\n", register.name)),
+            None => members.push_str(&format!("\t// {} has no offset\n", register.name)),
+        }
+    }
+
+    Some(format!(
+        "/// {0} registers at their hardware addresses\n\
+        struct {4}Peripheral {{\n\
+        \tstatic constexpr uintptr_t base_address = {1:#x};\n\
+        \n\
+        \texplicit {4}Peripheral(uintptr_t base = base_address) : {2} {{}};\n\
+        \n\
+        {3}\
+        }};\n\n",
+        family.register_family,
+        base_address,
+        initializers.join(", "),
+        members,
+        identifier(&family.register_family)
+    ))
+}
+
+fn create_file_comment(family: &RegisterFamily) -> String {
+    if family.description.is_none() && family.long_description.is_none() {
+        return String::new();
+    }
+    let mut comment = String::from("/// @file\n");
+    comment.push_str(&create_doxygen_comment("", &family.description, &family.long_description, &[]));
+    comment.push('\n');
+    comment
+}
+
+fn create_field_comment(field: &Field, extra: Option<&str>) -> String {
+    let access = match (field.read, field.write) {
+        (true, true) => "read-write",
+        (true, false) => "read-only",
+        (false, true) => "write-only",
+        (false, false) => "no access",
+    };
+    let signedness = if field.negative.unwrap_or(false) { "signed" } else { "unsigned" };
+
+    let mut details = vec![format!("Bits {}:{}, {}, {}", field.msb, field.lsb, access, signedness)];
+    details.extend(extra.map(String::from));
+    create_doxygen_comment("\t", &field.description, &field.long_description, &details)
+}
+
+// Builds a /// block from the description, long description and details, with the paragraphs
+// separated by empty /// lines
+fn create_doxygen_comment(indent: &str, description: &Option<String>, long_description: &Option<String>, details: &[String]) -> String {
+    let mut paragraphs: Vec<String> = Vec::new();
+    paragraphs.extend(description.iter().map(|text| text.trim().to_string()));
+    paragraphs.extend(long_description.iter().map(|text| text.trim().to_string()));
+    paragraphs.extend(details.iter().cloned());
+
+    paragraphs
+        .iter()
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| {
+            paragraph
+                .lines()
+                .map(|line| if line.trim().is_empty() { format!("{}///\n", indent) } else { format!("{}/// {}\n", indent, line.trim_end()) })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join(&format!("{}///\n", indent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emits_descriptions_as_doxygen_comments() {
+        let register = Register {
+            name: String::from("CTRL"),
+            size: 8,
+            description: Some(String::from("Control")),
+            long_description: Some(String::from("First paragraph.\n\nSecond paragraph.")),
+            fields: vec![Field { name: String::from("en"), lsb: 0, msb: 0, read: true, write: false, description: Some(String::from("Enable")), ..Default::default() }],
+            ..Default::default()
+        };
+
+        let class = create_register_class(&register, false);
+        assert!(class.starts_with("/// Control\n///\n/// First paragraph.\n///\n/// Second paragraph.\n///\n/// 8-bit register\nclass CTRL"));
+        assert!(class.contains("\t/// Enable\n\t///\n\t/// Bits 0:0, read-only, unsigned\n\tinline uint8_t get_en()"));
+    }
+
+    #[test]
+    fn maps_registers_with_offsets_into_the_peripheral() {
+        let family = RegisterFamily {
+            register_family: String::from("Hif"),
+            base_address: Some(0x4000_0000),
+            registers: vec![
+                Register { name: String::from("CTRL"), size: 32, offset: Some(0x10), ..Default::default() },
+                Register { name: String::from("SHADOW"), size: 32, ..Default::default() },
+            ],
+            ..Default::default()
+        };
+
+        assert!(create_register_class(&family.registers[0], true)
+            .contains("\tinline void modify(volatile uint32_t *address, Modifier modifier) { read_from(address); modifier(*this); write_to(address); };\n};"));
+        assert_eq!(
+            create_peripheral_struct(&family).unwrap(),
+            "/// Hif registers at their hardware addresses\n\
+            struct HifPeripheral {\n\
+            \tstatic constexpr uintptr_t base_address = 0x40000000;\n\
+            \n\
+            \texplicit HifPeripheral(uintptr_t base = base_address) : CTRL(base + 0x10) {};\n\
+            \n\
+            \tMmioRegister<::CTRL> CTRL;\n\
+            \t// SHADOW has no offset\n\
+            };\n\n"
+        );
+        let numbered = RegisterFamily { register_family: String::from("8Bit"), ..family.clone() };
+        assert!(create_peripheral_struct(&numbered).unwrap().contains("struct _8BitPeripheral {\n"));
+        assert!(create_peripheral_struct(&RegisterFamily { base_address: None, ..family }).is_none());
     }
 }
@@ -0,0 +1,228 @@
+use std::io;
+
+use crate::reg_gen::output_handling::{escape_xml, write_generated_file};
+use crate::reg_gen::register::*;
+
+// Bits shown per row of a register's layout diagram
+const DIAGRAM_ROW_BITS: u8 = 16;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 0; display: flex; }
+nav { position: sticky; top: 0; height: 100vh; overflow-y: auto; min-width: 16em; padding: 1em; background: #f3f3f3; box-sizing: border-box; }
+nav ul { list-style: none; padding: 0; }
+nav li { margin: 0.2em 0; }
+main { padding: 1em 2em; flex: 1; }
+#search { width: 100%; box-sizing: border-box; padding: 0.3em; }
+table { border-collapse: collapse; margin: 0.5em 0 1.5em 0; }
+th, td { border: 1px solid #999; padding: 0.2em 0.5em; }
+.layout td, .layout th { text-align: center; font-size: 0.85em; min-width: 1.6em; }
+.layout .unused { background: #ddd; color: #777; }
+.layout .field { background: #dbe9f7; }
+.address { color: #555; }
+section { border-top: 1px solid #ccc; padding-top: 0.5em; }
+.hidden { display: none; }
+";
+
+const SCRIPT: &str = "\
+document.getElementById('search').addEventListener('input', function () {
+\tvar query = this.value.toLowerCase();
+\tdocument.querySelectorAll('[data-search]').forEach(function (element) {
+\t\telement.classList.toggle('hidden', element.dataset.search.indexOf(query) < 0);
+\t});
+});
+";
+
+pub fn generate_html(register_family: &RegisterFamily) -> Result<(), io::Error> {
+    let file_name = format!("{}.html", register_family.register_family);
+    write_generated_file(&file_name, &create_html_manual(register_family))
+}
+
+// A single self contained page, with no external assets so it works offline
+pub fn create_html_manual(register_family: &RegisterFamily) -> String {
+    let title = format!("{} Registers", escape_xml(&register_family.register_family));
+
+    let mut index = String::new();
+    let mut sections = String::new();
+    for register in &register_family.registers {
+        let search = escape_xml(&search_text(register));
+        let address = register_family
+            .register_address(register)
+            .map(|address| format!(" <span class=\"address\">{:#x}</span>", address))
+            .unwrap_or_default();
+        index.push_str(&format!(
+            "<li data-search=\"{}\"><a href=\"#{}\">{}</a>{}</li>\n",
+            search, register_anchor(register), escape_xml(&register.name), address
+        ));
+        sections.push_str(&create_register_section(register_family, register, &search));
+    }
+
+    let mut description = String::new();
+    push_descriptions(&mut description, &register_family.description, &register_family.long_description);
+
+    format!(
+        "<!DOCTYPE html>\n\
+        <html lang=\"en\">\n\
+        <head>\n\
+        <meta charset=\"utf-8\">\n\
+        <title>{0}</title>\n\
+        <style>\n{1}</style>\n\
+        </head>\n\
+        <body>\n\
+        <nav>\n\
+        <input id=\"search\" type=\"search\" placeholder=\"Search registers and fields\">\n\
+        <ul>\n{2}</ul>\n\
+        </nav>\n\
+        <main>\n\
+        <h1 id=\"index\">{0}</h1>\n\
+        {3}\
+        {4}\
+        </main>\n\
+        <script>\n{5}</script>\n\
+        </body>\n\
+        </html>\n",
+        title, STYLE, index, description, sections, SCRIPT
+    )
+}
+
+fn create_register_section(register_family: &RegisterFamily, register: &Register, search: &str) -> String {
+    let mut section = format!(
+        "<section id=\"{}\" data-search=\"{}\">\n<h2>{}</h2>\n",
+        register_anchor(register), search, escape_xml(&register.name)
+    );
+    push_descriptions(&mut section, &register.description, &register.long_description);
+
+    let mut details = format!("{}-bit register", register.size);
+    if let Some(offset) = register.offset {
+        details.push_str(&format!(", offset {:#x}", offset));
+    }
+    if let Some(address) = register_family.register_address(register) {
+        details.push_str(&format!(", address {:#x}", address));
+    }
+    section.push_str(&format!("<p class=\"address\">{}</p>\n", details));
+
+    section.push_str(&create_layout_diagram(register));
+    section.push_str(&create_field_table(register));
+    section.push_str("<p><a href=\"#index\">Back to index</a></p>\n</section>\n");
+    section
+}
+
+// One row of bit numbers and one row of fields per DIAGRAM_ROW_BITS bits, each field linking to
+// its row in the field table
+fn create_layout_diagram(register: &Register) -> String {
+    let mut diagram = String::from("<table class=\"layout\">\n");
+    let row_bits = DIAGRAM_ROW_BITS.min(register.size);
+    let mut row_msb = register.size;
+    while row_msb > 0 {
+        let msb = row_msb - 1;
+        let lsb = row_msb - row_bits;
+
+        diagram.push_str("<tr>");
+        for bit in (lsb..=msb).rev() {
+            diagram.push_str(&format!("<th>{}</th>", bit));
+        }
+        diagram.push_str("</tr>\n<tr>");
+        for segment in register.bit_segments(msb, lsb) {
+            let span = segment.msb - segment.lsb + 1;
+            match segment.field {
+                Some(field) => diagram.push_str(&format!(
+                    "<td class=\"field\" colspan=\"{}\"><a href=\"#{}\">{}</a></td>",
+                    span, field_anchor(register, field), escape_xml(&field.name)
+                )),
+                None => diagram.push_str(&format!("<td class=\"unused\" colspan=\"{}\">&mdash;</td>", span)),
+            }
+        }
+        diagram.push_str("</tr>\n");
+
+        row_msb = lsb;
+    }
+    diagram.push_str("</table>\n");
+    diagram
+}
+
+fn create_field_table(register: &Register) -> String {
+    let mut table = String::from(
+        "<table>\n<tr><th>Field</th><th>Bits</th><th>Access</th><th>Signed</th><th>Reset</th><th>Description</th><th>Enums</th></tr>\n"
+    );
+    let mut fields: Vec<&Field> = register.fields.iter().collect();
+    fields.sort_by_key(|field| std::cmp::Reverse(field.msb));
+
+    for field in fields {
+        let mut description = String::new();
+        push_descriptions(&mut description, &field.description, &field.long_description);
+        let enums = field.enums
+            .iter()
+            .flatten()
+            .map(|enum_value| format!("{} = {}", escape_xml(&enum_value.name), enum_value.value))
+            .collect::<Vec<String>>()
+            .join("<br>");
+
+        table.push_str(&format!(
+            "<tr id=\"{}\"><td>{}</td><td>{}:{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            field_anchor(register, field),
+            escape_xml(&field.name),
+            field.msb,
+            field.lsb,
+            field.access(),
+            if field.negative.unwrap_or(false) { "yes" } else { "no" },
+            field.reset.map(|reset| format!("{:#x}", reset)).unwrap_or_default(),
+            description,
+            enums
+        ));
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+// Paragraphs of the long description are separated by empty lines
+fn push_descriptions(html: &mut String, description: &Option<String>, long_description: &Option<String>) {
+    for text in description.iter().chain(long_description) {
+        for paragraph in text.split("\n\n").map(str::trim).filter(|paragraph| !paragraph.is_empty()) {
+            html.push_str(&format!("<p>{}</p>\n", escape_xml(paragraph)));
+        }
+    }
+}
+
+fn search_text(register: &Register) -> String {
+    let mut text = register.name.clone();
+    text.extend(register.description.iter().map(|description| format!(" {}", description)));
+    for field in &register.fields {
+        text.push_str(&format!(" {}", field.name));
+        text.extend(field.description.iter().map(|description| format!(" {}", description)));
+    }
+    text.to_lowercase()
+}
+
+fn register_anchor(register: &Register) -> String {
+    format!("register-{}", register.name)
+}
+
+fn field_anchor(register: &Register, field: &Field) -> String {
+    format!("register-{}-{}", register.name, field.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_and_escapes_registers() {
+        let register_family = RegisterFamily {
+            register_family: String::from("Test"),
+            registers: vec![Register {
+                name: String::from("CTRL"),
+                size: 8,
+                offset: Some(0x4),
+                fields: vec![Field { name: String::from("en"), lsb: 0, msb: 0, read: true, reset: Some(1), description: Some(String::from("Enables <everything>")), ..Default::default() }],
+                ..Default::default()
+            }],
+            base_address: Some(0x1000),
+            ..Default::default()
+        };
+
+        let html = create_html_manual(&register_family);
+        assert!(html.contains("<li data-search=\"ctrl en enables &lt;everything&gt;\"><a href=\"#register-CTRL\">CTRL</a> <span class=\"address\">0x1004</span></li>"));
+        assert!(html.contains("<td class=\"unused\" colspan=\"7\">&mdash;</td><td class=\"field\" colspan=\"1\"><a href=\"#register-CTRL-en\">en</a></td>"));
+        assert!(html.contains("<tr id=\"register-CTRL-en\"><td>en</td><td>0:0</td><td>RO</td><td>no</td><td>0x1</td><td><p>Enables &lt;everything&gt;</p>\n</td>"));
+        assert!(!html.contains("http"), "the manual must not load anything over the network");
+    }
+}
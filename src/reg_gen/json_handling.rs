@@ -1,12 +1,150 @@
-use std::{fs::File, io::Read};
+use std::{fs::{self, OpenOptions}, io::{self, Write}};
 
+use serde_json::Value;
+
+use crate::reg_gen::format_handling::{discard_comments, ConfigFormat};
 use crate::reg_gen::register::*;
+use crate::reg_gen::validation::*;
+
+// Reads a configuration in the format of its extension, or --format
+pub fn pull_existing_json(path: &String) -> Result<RegisterFamily, io::Error> {
+    read_register_family_as(path, ConfigFormat::of_path(path))
+}
+
+// Reads a configuration, checking it against the schema first so mistakes are reported with
+// their location in the file rather than as a bare deserialisation error
+pub fn read_register_family_as(path: &String, format: ConfigFormat) -> Result<RegisterFamily, io::Error> {
+    let contents = fs::read_to_string(path)
+        .map_err(|why| io::Error::new(why.kind(), format!("Couldn't read {}: {}", path, why)))?;
+    let json: Value = format
+        .parse(&contents)
+        .map_err(|why| invalid_data(format!("{} isn't valid {}: {}", path, format.name(), why)))?;
+
+    let schema = register_family_schema();
+    let validator = jsonschema::draft202012::new(&schema).expect("The generated schema is invalid");
+    let problems: Vec<String> = validator
+        .iter_errors(&json)
+        .map(|error| {
+            let location = error.instance_path().to_string();
+            if location.is_empty() {
+                error.to_string()
+            } else {
+                format!("{}: {}", location, error)
+            }
+        })
+        .collect();
+    if !problems.is_empty() {
+        return Err(invalid_data(format!("{} doesn't match the schema:\n  {}", path, problems.join("\n  "))));
+    }
+
+    serde_json::from_value(json).map_err(|why| invalid_data(format!("Couldn't read {}: {}", path, why)))
+}
+
+// JSON Schema (draft 2020-12) of the configuration format, derived from the model
+pub fn register_family_schema() -> Value {
+    serde_json::to_value(schemars::schema_for!(RegisterFamily)).unwrap()
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Sorts fields by lsb and derives the family widths from the registers present, so that the same
+// register map always produces the same file no matter how it was edited
+pub fn canonicalize_register_family(register_family: &mut RegisterFamily) {
+    for register in register_family.registers.iter_mut() {
+        register.fields.sort_by_key(|field| (field.lsb, field.msb));
+    }
+
+    let mut widths: Vec<u8> = register_family.registers.iter().map(|register| register.size).collect();
+    widths.sort();
+    widths.dedup();
+    register_family.register_family_widths = widths;
+}
+
+// The canonical text of a family, as written by every command and checked by fmt --check
+pub fn format_register_family(register_family: &RegisterFamily, format: ConfigFormat) -> Result<String, io::Error> {
+    let mut canonical = register_family.clone();
+    canonicalize_register_family(&mut canonical);
+    format
+        .serialize(&canonical)
+        .map_err(|why| invalid_data(format!("Couldn't write {} as {}: {}", canonical.register_family, format.name(), why)))
+}
+
+// Writes a configuration in the format of its extension, or --format
+pub fn write_register_family(path: &String, register_family: &RegisterFamily) -> Result<(), io::Error> {
+    write_register_family_as(path, register_family, ConfigFormat::of_path(path))
+}
+
+// Comments can't survive a YAML or TOML file being rewritten through serde, so files with them are
+// only rewritten when --discard-comments is given
+pub fn check_comments_kept(path: &String, format: ConfigFormat) -> Result<(), io::Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) if !discard_comments() && format.has_comments(&contents) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} has comments, which rewriting it would lose. Pass --discard-comments to rewrite it anyway", path),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Validates the family and writes it out in canonical form. Every command that modifies a
+// configuration goes through here so none of them can leave behind one that won't generate.
+pub fn write_register_family_as(path: &String, register_family: &RegisterFamily, format: ConfigFormat) -> Result<(), io::Error> {
+    let mut register_family = register_family.clone();
+    canonicalize_register_family(&mut register_family);
+    let mut problems = validate_register_family(&register_family).err().unwrap_or_default();
+    if format == ConfigFormat::Toml {
+        problems.extend(validate_toml_integers(&register_family));
+    }
+    if !problems.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Refusing to write {}:\n  {}", path, problems.join("\n  ")),
+        ));
+    }
+    check_comments_kept(path, format)?;
+
+    // Serialise before truncating so a failure leaves the file as it was
+    let contents = format_register_family(&register_family, format)?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .map_err(|why| io::Error::new(why.kind(), format!("Couldn't open {}: {}", path, why)))?;
+
+    match file.write_all(contents.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(why) => Err(io::Error::new(why.kind(), format!("Couldn't write to {}: {}", path, why))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_form_sorts_fields_and_derives_widths() {
+        let field = |name: &str, lsb: u8| Field { name: name.to_string(), lsb, msb: lsb, read: true, ..Default::default() };
+        let mut register_family = RegisterFamily {
+            register_family: String::from("Test"),
+            register_family_widths: vec![64, 8, 8],
+            registers: vec![
+                Register { name: String::from("A"), size: 32, fields: vec![field("high", 4), field("low", 0)], ..Default::default() },
+                Register { name: String::from("B"), size: 8, ..Default::default() },
+            ],
+            ..Default::default()
+        };
 
-pub fn pull_existing_json(path: &String) -> RegisterFamily {
-    let mut file = File::open(path).expect("Couldn't open the input file!");
+        canonicalize_register_family(&mut register_family);
+        assert_eq!(register_family.register_family_widths, vec![8, 32]);
+        assert_eq!(register_family.registers[0].fields[0].name, "low");
+    }
 
-    // Read in the string as json
-    let mut json_string = String::new();
-    file.read_to_string(&mut json_string).expect("Failed to read input file as string!");
-    return serde_json::from_str(&json_string).expect("Failed to interpret JSON!");
+    #[test]
+    fn published_schema_is_up_to_date() {
+        let published = include_str!("../../docs/register_family.schema.json");
+        assert_eq!(serde_json::to_string_pretty(&register_family_schema()).unwrap() + "\n", published, "run `register_generator schema > docs/register_family.schema.json`");
+    }
 }
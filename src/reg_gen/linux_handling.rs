@@ -0,0 +1,207 @@
+use std::{collections::HashMap, io};
+
+use crate::reg_gen::output_handling::{identifier, write_generated_file};
+use crate::reg_gen::register::*;
+
+pub fn generate_linux_header(register_family: &RegisterFamily) -> Result<(), io::Error> {
+    let header = create_linux_header(register_family).map_err(|clashes| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("Couldn't generate kernel macros:\n  {}", clashes.join("\n  ")))
+    })?;
+    let file_name = format!("{}_regs.h", register_family.register_family.to_lowercase());
+    write_generated_file(&file_name, &header)
+}
+
+// Kernel style macros: FAMILY_REGISTER is the register's offset from FAMILY_BASE, and
+// FAMILY_REGISTER_FIELD its mask for FIELD_GET() and FIELD_PREP(), followed by the field's enum
+// values unshifted. Signed fields also get inline helpers that sign extend. Returns the macro
+// names that would be defined twice, like register A_B and field B of register A.
+pub fn create_linux_header(register_family: &RegisterFamily) -> Result<String, Vec<String>> {
+    let family = macro_name(&identifier(&register_family.register_family));
+    let mut defines = String::new();
+    let mut helpers = String::new();
+    let mut defined: HashMap<String, String> = HashMap::new();
+    let mut clashes = Vec::new();
+    let mut define = |text: &mut String, name: String, value: String, origin: String| {
+        if let Some(other) = defined.insert(name.clone(), origin.clone()) {
+            clashes.push(format!("{} and {} would both define {}", other, origin, name));
+        }
+        text.push_str(&format!("#define {}\t{}\n", name, value));
+    };
+
+    if let Some(base_address) = register_family.base_address {
+        define(&mut defines, format!("{}_BASE", family), format!("{:#x}", base_address), String::from("the base address"));
+    }
+
+    for register in &register_family.registers {
+        let (unsigned, signed, suffix) = if register.size == 64 { ("u64", "s64", "_ULL") } else { ("u32", "s32", "") };
+        let register_macro = format!("{}_{}", family, macro_name(&register.name));
+
+        defines.push('\n');
+        if let Some(description) = &register.description {
+            defines.push_str(&format!("/* {} */\n", comment(description)));
+        }
+        match register.offset {
+            Some(offset) => define(&mut defines, register_macro.clone(), format!("{:#x}", offset), format!("register {}", register.name)),
+            None => defines.push_str(&format!("/* {} has no offset */\n", register_macro)),
+        }
+
+        for field in &register.fields {
+            let field_macro = format!("{}_{}", register_macro, macro_name(&field.name));
+            let origin = format!("field {}.{}", register.name, field.name);
+            let mask = if field.msb == field.lsb {
+                format!("BIT{}({})", suffix, field.lsb)
+            } else {
+                format!("GENMASK{}({}, {})", suffix, field.msb, field.lsb)
+            };
+            let mut value = mask;
+            if let Some(description) = &field.description {
+                value.push_str(&format!("\t/* {} */", comment(description)));
+            }
+            define(&mut defines, field_macro.clone(), value, origin.clone());
+
+            for enum_value in field.enums.iter().flatten() {
+                // FIELD_PREP() refuses constants with bits outside the mask, so negative values are
+                // truncated to the field, with the signed value alongside
+                let value = if field.negative.unwrap_or(false) {
+                    format!("{:#x}\t/* {} */", enum_value.value as u64 & field.get_value(u64::MAX), enum_value.value)
+                } else {
+                    format!("{:#x}", enum_value.value)
+                };
+                define(&mut defines, format!("{}_{}", field_macro, macro_name(&enum_value.name)), value, format!("{} value {}", origin, enum_value.name));
+            }
+
+            // FIELD_GET() zero extends, and FIELD_PREP() needs negative values masked to fit
+            if field.negative.unwrap_or(false) {
+                let function = format!("{}_{}_{}", identifier(&register_family.register_family), register.name, field.name).to_lowercase();
+                helpers.push_str(&format!(
+                    "\nstatic inline {signed} {function}_get({unsigned} reg)\n\
+                    {{\n\
+                    \treturn sign_extend{bits}(FIELD_GET({field_macro}, reg), {sign_bit});\n\
+                    }}\n\
+                    \n\
+                    static inline {unsigned} {function}_prep({signed} value)\n\
+                    {{\n\
+                    \treturn FIELD_PREP({field_macro}, ({unsigned})value & field_max({field_macro}));\n\
+                    }}\n",
+                    bits = if register.size == 64 { 64 } else { 32 },
+                    sign_bit = field.width() - 1,
+                ));
+            }
+        }
+    }
+
+    if !clashes.is_empty() {
+        return Err(clashes);
+    }
+
+    let guard = format!("{}_REGS_H", family);
+    let description = register_family
+        .description
+        .as_ref()
+        .map(|description| format!("/* {} */\n\n", comment(description)))
+        .unwrap_or_default();
+    Ok(format!(
+        "/* This file was automatically generated by a register generation tool\n \
+        * https://github.com/regerj/register_generator\n \
+        * Any changes to this file may be overwritten on subsequent generations\n \
+        */\n\
+        \n\
+        #ifndef {guard}\n\
+        #define {guard}\n\
+        \n\
+        #include <linux/bitfield.h>\n\
+        #include <linux/bitops.h>\n\
+        #include <linux/bits.h>\n\
+        #include <linux/types.h>\n\
+        \n\
+        {description}\
+        {defines}\
+        {helpers}\
+        \n\
+        #endif /* {guard} */\n"
+    ))
+}
+
+fn macro_name(name: &str) -> String {
+    name.to_uppercase()
+}
+
+// Descriptions on one line, without anything that would end the comment early
+fn comment(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ").replace("*/", "* /")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family() -> RegisterFamily {
+        RegisterFamily {
+            register_family: String::from("Hif"),
+            base_address: Some(0x4000_0000),
+            registers: vec![Register {
+                name: String::from("Ctrl"),
+                size: 32,
+                offset: Some(0x10),
+                fields: vec![
+                    Field { name: String::from("en"), lsb: 0, msb: 0, read: true, write: true, description: Some(String::from("Enable")), ..Default::default() },
+                    Field {
+                        name: String::from("mode"),
+                        lsb: 4,
+                        msb: 5,
+                        read: true,
+                        write: true,
+                        enums: Some(vec![EnumValue { name: String::from("fast"), value: 2 }]),
+                        ..Default::default()
+                    },
+                    Field {
+                        name: String::from("trim"),
+                        lsb: 8,
+                        msb: 15,
+                        read: true,
+                        write: true,
+                        negative: Some(true),
+                        enums: Some(vec![EnumValue { name: String::from("low"), value: -1 }, EnumValue { name: String::from("high"), value: 3 }]),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn defines_offsets_masks_and_sign_extension_helpers() {
+        let header = create_linux_header(&family()).unwrap();
+        assert!(header.contains("\
+            #define HIF_BASE\t0x40000000\n\
+            \n\
+            #define HIF_CTRL\t0x10\n\
+            #define HIF_CTRL_EN\tBIT(0)\t/* Enable */\n\
+            #define HIF_CTRL_MODE\tGENMASK(5, 4)\n\
+            #define HIF_CTRL_MODE_FAST\t0x2\n\
+            #define HIF_CTRL_TRIM\tGENMASK(15, 8)\n\
+            #define HIF_CTRL_TRIM_LOW\t0xff\t/* -1 */\n\
+            #define HIF_CTRL_TRIM_HIGH\t0x3\t/* 3 */\n"));
+        assert!(header.contains("\treturn sign_extend32(FIELD_GET(HIF_CTRL_TRIM, reg), 7);\n"));
+        assert!(header.contains("static inline u32 hif_ctrl_trim_prep(s32 value)\n"));
+    }
+
+    #[test]
+    fn makes_family_names_identifiers() {
+        let mut register_family = family();
+        register_family.register_family = String::from("8Bit");
+        let header = create_linux_header(&register_family).unwrap();
+        assert!(header.contains("#ifndef _8BIT_REGS_H\n"));
+        assert!(header.contains("#define _8BIT_CTRL_EN\tBIT(0)"));
+        assert!(header.contains("static inline s32 _8bit_ctrl_trim_get(u32 reg)\n"));
+    }
+
+    #[test]
+    fn refuses_macros_defined_twice() {
+        let mut register_family = family();
+        register_family.registers.push(Register { name: String::from("Ctrl_en"), size: 8, offset: Some(0x14), ..Default::default() });
+        assert_eq!(create_linux_header(&register_family).unwrap_err(), ["field Ctrl.en and register Ctrl_en would both define HIF_CTRL_EN"]);
+    }
+}
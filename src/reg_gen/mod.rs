@@ -1,3 +1,18 @@
 pub mod header_handling;
 pub mod json_handling;
 pub mod register;
+pub mod validation;
+pub mod text_table;
+pub mod output_handling;
+pub mod trace_handling;
+pub mod diff;
+pub mod family_handling;
+pub mod format_handling;
+pub mod html_handling;
+pub mod reference_handling;
+pub mod diagram_handling;
+pub mod spreadsheet_handling;
+pub mod c_import_handling;
+pub mod bitfield_import_handling;
+pub mod linux_handling;
+pub mod python_handling;
@@ -0,0 +1,45 @@
+use std::{fs, io};
+
+// Writes a file made by one of the generate backends, naming it in any error
+pub fn write_generated_file(file_name: &str, contents: &str) -> Result<(), io::Error> {
+    fs::write(file_name, contents)
+        .map_err(|why| io::Error::new(why.kind(), format!("Couldn't write to {}: {}", file_name, why)))?;
+    println!("Wrote {}", file_name);
+    Ok(())
+}
+
+// Text for HTML and SVG, in element content or double quoted attributes
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// A name that can be used in C, C++ and Python source, for family names which unlike register and
+// field names are free text. Other characters become underscores, and a leading digit gets one.
+pub fn identifier(name: &str) -> String {
+    let identifier: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", identifier)
+    } else {
+        identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn makes_family_names_identifiers() {
+        assert_eq!(identifier("Hif"), "Hif");
+        assert_eq!(identifier("8Bit"), "_8Bit");
+        assert_eq!(identifier("PCIe Gen-5"), "PCIe_Gen_5");
+    }
+
+    #[test]
+    fn escapes_markup_characters() {
+        assert_eq!(escape_xml("<a href=\"x\">R&D</a>"), "&lt;a href=&quot;x&quot;&gt;R&amp;D&lt;/a&gt;");
+    }
+}
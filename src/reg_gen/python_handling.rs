@@ -0,0 +1,312 @@
+use std::io;
+
+use crate::reg_gen::output_handling::{identifier, write_generated_file};
+use crate::reg_gen::register::*;
+
+// Python keywords, and the names every register class already uses
+const RESERVED_NAMES: [&str; 42] = [
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else",
+    "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise",
+    "return", "try", "while", "with", "yield", "from_int", "to_int", "SIZE", "OFFSET", "ADDRESS", "_value", "_fields",
+];
+
+// Shared by every class, so the generated module needs nothing beyond the standard library
+const HELPERS: &str = "\
+def _get_field(raw, lsb, width, signed):
+    value = (raw >> lsb) & ((1 << width) - 1)
+    if signed and value & (1 << (width - 1)):
+        value -= 1 << width
+    return value
+
+
+def _set_field(raw, lsb, width, value, signed, name):
+    if signed:
+        low, high = -(1 << (width - 1)), (1 << (width - 1)) - 1
+    else:
+        low, high = 0, (1 << width) - 1
+    if not isinstance(value, int) or not low <= value <= high:
+        raise ValueError(f\"{name} must be an integer from {low} to {high}, not {value!r}\")
+    mask = ((1 << width) - 1) << lsb
+    return (raw & ~mask) | ((value << lsb) & mask)
+
+
+def _check_raw(cls, value):
+    if not isinstance(value, int) or not 0 <= value < (1 << cls.SIZE):
+        raise ValueError(f\"{cls.__name__} values are {cls.SIZE}-bit, not {value!r}\")
+    return value
+";
+
+pub fn generate_python(register_family: &RegisterFamily) -> Result<(), io::Error> {
+    // The module is imported by its file name, so that has to be an identifier too
+    let file_name = format!("{}_registers.py", identifier(&register_family.register_family).to_lowercase());
+    write_generated_file(&file_name, &create_python_module(register_family))
+}
+
+// A class per register wrapping its raw value, with a property per field that reads the field
+// like the C++ get method and checks writes like the set method
+pub fn create_python_module(register_family: &RegisterFamily) -> String {
+    let mut module = format!(
+        "\"\"\"{} registers.\n\
+        \n\
+        This file was automatically generated by a register generation tool\n\
+        https://github.com/regerj/register_generator\n\
+        Any changes to this file may be overwritten on subsequent generations\n",
+        escape_docstring(&register_family.register_family)
+    );
+    for paragraph in paragraphs(&register_family.description, &register_family.long_description) {
+        module.push_str(&format!("\n{}\n", escape_docstring(&paragraph)));
+    }
+    module.push_str("\"\"\"\n\n");
+    if let Some(base_address) = register_family.base_address {
+        module.push_str(&format!("BASE_ADDRESS = {:#x}\n\n", base_address));
+    }
+    module.push_str(&format!("\n{}", HELPERS));
+
+    for register in &register_family.registers {
+        module.push_str(&format!("\n\n{}", create_register_class(register_family, register)));
+    }
+
+    let classes: Vec<String> = register_family.registers.iter().map(|register| format!("{},", python_name(&register.name))).collect();
+    module.push_str(&format!("\n\nREGISTERS = ({})\n", classes.join(" ")));
+    module
+}
+
+fn create_register_class(register_family: &RegisterFamily, register: &Register) -> String {
+    let name = python_name(&register.name);
+    let mut docstring = paragraphs(&register.description, &register.long_description);
+    docstring.push(format!("{}-bit register.", register.size));
+    let mut class = format!("class {}:\n{}\n    SIZE = {}\n", name, indented_docstring(&docstring), register.size);
+    if let Some(offset) = register.offset {
+        class.push_str(&format!("    OFFSET = {:#x}\n", offset));
+    }
+    if let (Some(_), Some(address)) = (register_family.base_address, register_family.register_address(register)) {
+        class.push_str(&format!("    ADDRESS = {:#x}\n", address));
+    }
+    for field in &register.fields {
+        for enum_value in field.enums.iter().flatten() {
+            // Enum names are free text, unlike field names
+            class.push_str(&format!("    {}_{} = {}\n", field.name.to_uppercase(), identifier(&enum_value.name).to_uppercase(), enum_value.value));
+        }
+    }
+
+    class.push_str(&format!(
+        "\n    def __init__(self, value=0):\n        \
+        self._value = _check_raw(type(self), value)\n\
+        \n    \
+        @classmethod\n    \
+        def from_int(cls, value):\n        \
+        return cls(value)\n\
+        \n    \
+        def to_int(self):\n        \
+        return self._value\n\
+        \n    \
+        def __int__(self):\n        \
+        return self._value\n\
+        \n    \
+        def __eq__(self, other):\n        \
+        return type(self) is type(other) and self._value == other._value\n\
+        \n    \
+        def __repr__(self):\n        \
+        fields = \"\".join(f\", {{name}}={{value}}\" for name, value in self._fields())\n        \
+        return f\"{name}({{self._value:#0{width}x}}{{fields}})\"\n",
+        width = register.size as usize / 4 + 2
+    ));
+
+    // Readable fields in the repr, with enum values by name
+    let mut repr_fields = String::new();
+    for field in register.fields.iter().filter(|field| field.read) {
+        let property = python_name(&field.name);
+        let names: Vec<String> = field.enums
+            .iter()
+            .flatten()
+            .map(|enum_value| format!("{}: {}", enum_value.value, string_literal(&enum_value.name)))
+            .collect();
+        if names.is_empty() {
+            repr_fields.push_str(&format!("        yield \"{}\", self.{}\n", field.name, property));
+        } else {
+            repr_fields.push_str(&format!(
+                "        yield \"{}\", {{{}}}.get(self.{}, self.{})\n",
+                field.name, names.join(", "), property, property
+            ));
+        }
+    }
+    if repr_fields.is_empty() {
+        repr_fields.push_str("        return iter(())\n");
+    }
+    class.push_str(&format!("\n    def _fields(self):\n{}", repr_fields));
+
+    for field in &register.fields {
+        class.push_str(&create_field_property(field));
+    }
+    class
+}
+
+// Write-only fields have no getter and read-only fields no setter, so using them the wrong way
+// raises AttributeError like the missing C++ method fails to compile
+fn create_field_property(field: &Field) -> String {
+    if !field.read && !field.write {
+        return String::new();
+    }
+    let property = python_name(&field.name);
+    let signed = if field.negative.unwrap_or(false) { "True" } else { "False" };
+    let mut docstring = paragraphs(&field.description, &field.long_description);
+    let mut details = format!("Bits {}:{}, {}", field.msb, field.lsb, match (field.read, field.write) {
+        (true, true) => "read-write",
+        (true, false) => "read-only",
+        _ => "write-only",
+    });
+    if field.negative.unwrap_or(false) {
+        details.push_str(", signed");
+    }
+    docstring.push(format!("{}.", details));
+
+    let getter = format!(
+        "\n    def _get_{0}(self):\n        \
+        return _get_field(self._value, {1}, {2}, {3})\n",
+        property, field.lsb, field.width(), signed
+    );
+    let setter = format!(
+        "\n    def _set_{0}(self, value):\n        \
+        self._value = _set_field(self._value, {1}, {2}, value, {3}, \"{4}\")\n",
+        property, field.lsb, field.width(), signed, field.name
+    );
+    let docstring = docstring
+        .iter()
+        .map(|paragraph| paragraph.replace('\\', "\\\\").replace('"', "\\\""))
+        .collect::<Vec<String>>()
+        .join("\\n\\n");
+    match (field.read, field.write) {
+        (true, true) => format!("{}{}\n    {} = property(_get_{}, _set_{}, doc=\"{}\")\n", getter, setter, property, property, property, docstring),
+        (true, false) => format!("{}\n    {} = property(_get_{}, doc=\"{}\")\n", getter, property, property, docstring),
+        _ => format!("{}\n    {} = property(None, _set_{}, doc=\"{}\")\n", setter, property, property, docstring),
+    }
+}
+
+// Field and register names that would break Python get an underscore
+fn python_name(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else if RESERVED_NAMES.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn paragraphs(description: &Option<String>, long_description: &Option<String>) -> Vec<String> {
+    description
+        .iter()
+        .chain(long_description)
+        .flat_map(|text| text.split("\n\n"))
+        .map(|paragraph| paragraph.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect()
+}
+
+fn indented_docstring(paragraphs: &[String]) -> String {
+    let text = paragraphs.iter().map(|paragraph| escape_docstring(paragraph)).collect::<Vec<String>>().join("\n\n    ");
+    format!("    \"\"\"{}\"\"\"\n", text)
+}
+
+// Backslashes would start escapes, and three quotes would end the docstring early. A quote at the
+// end would run into the closing quotes.
+fn escape_docstring(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace("\"\"\"", "\\\"\\\"\\\"");
+    match escaped.strip_suffix('"') {
+        Some(rest) if !rest.ends_with('\\') => format!("{}\\\"", rest),
+        _ => escaped,
+    }
+}
+
+fn string_literal(text: &str) -> String {
+    let mut literal = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '\\' => literal.push_str("\\\\"),
+            '"' => literal.push_str("\\\""),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            ch if ch.is_control() => literal.push_str(&format!("\\x{:02x}", ch as u32)),
+            ch => literal.push(ch),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn honours_access_and_signedness() {
+        let register_family = RegisterFamily {
+            register_family: String::from("Hif"),
+            registers: vec![Register {
+                name: String::from("CTRL"),
+                size: 16,
+                offset: Some(0x4),
+                fields: vec![
+                    Field { name: String::from("mode"), lsb: 0, msb: 1, read: true, write: true, enums: Some(vec![EnumValue { name: String::from("fast"), value: 2 }]), ..Default::default() },
+                    Field { name: String::from("trim"), lsb: 4, msb: 7, read: true, negative: Some(true), ..Default::default() },
+                    Field { name: String::from("class"), lsb: 8, msb: 8, write: true, ..Default::default() },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = create_python_module(&register_family);
+        assert!(module.contains("class CTRL:\n    \"\"\"16-bit register.\"\"\"\n\n    SIZE = 16\n    OFFSET = 0x4\n    MODE_FAST = 2\n"));
+        assert!(module.contains("    mode = property(_get_mode, _set_mode, doc=\"Bits 1:0, read-write.\")\n"));
+        assert!(module.contains("        return _get_field(self._value, 4, 4, True)\n"));
+        assert!(module.contains("    trim = property(_get_trim, doc=\"Bits 7:4, read-only, signed.\")\n"));
+        assert!(module.contains("        self._value = _set_field(self._value, 8, 1, value, False, \"class\")\n"));
+        assert!(module.contains("    class_ = property(None, _set_class_, doc=\"Bits 8:8, write-only.\")\n"));
+        assert!(module.contains("        yield \"mode\", {2: \"fast\"}.get(self.mode, self.mode)\n        yield \"trim\", self.trim\n"));
+        assert!(module.ends_with("REGISTERS = (CTRL,)\n"));
+    }
+
+    #[test]
+    fn escapes_family_docstrings() {
+        let register_family = RegisterFamily {
+            register_family: String::from("8Bit"),
+            description: Some(String::from(r#"Holds values "quoted""#)),
+            long_description: Some(String::from(r#"Matches \x00 and """ too"#)),
+            ..Default::default()
+        };
+
+        let module = create_python_module(&register_family);
+        assert!(module.contains("\nHolds values \"quoted\\\"\n"));
+        assert!(module.contains("\nMatches \\\\x00 and \\\"\\\"\\\" too\n"));
+    }
+
+    #[test]
+    fn makes_enum_names_valid_python() {
+        let register_family = RegisterFamily {
+            register_family: String::from("Hif"),
+            registers: vec![Register {
+                name: String::from("CTRL"),
+                size: 8,
+                fields: vec![Field {
+                    name: String::from("mode"),
+                    lsb: 0,
+                    msb: 1,
+                    read: true,
+                    enums: Some(vec![
+                        EnumValue { name: String::from("Fast-Mode"), value: 1 },
+                        EnumValue { name: String::from(r#"2X "turbo" \"#), value: 2 },
+                    ]),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let module = create_python_module(&register_family);
+        assert!(module.contains("    MODE_FAST_MODE = 1\n    MODE__2X__TURBO___ = 2\n"));
+        assert!(module.contains(r#"{1: "Fast-Mode", 2: "2X \"turbo\" \\"}.get(self.mode, self.mode)"#));
+    }
+}
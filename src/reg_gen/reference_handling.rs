@@ -0,0 +1,223 @@
+use std::io;
+
+use crate::reg_gen::output_handling::write_generated_file;
+use crate::reg_gen::register::*;
+
+// One row of a register's bit table, with the gaps between fields listed as reserved like a
+// datasheet would
+struct BitRow {
+    bits: String,
+    field: String,
+    access: String,
+    reset: String,
+    // Paragraphs of the description and long description
+    description: Vec<String>,
+}
+
+fn bit_rows(register: &Register) -> Vec<BitRow> {
+    if register.size == 0 {
+        return Vec::new();
+    }
+    register
+        .bit_segments(register.size - 1, 0)
+        .iter()
+        .map(|segment| {
+            let bits = if segment.msb == segment.lsb {
+                segment.msb.to_string()
+            } else {
+                format!("{}:{}", segment.msb, segment.lsb)
+            };
+            match segment.field {
+                Some(field) => BitRow {
+                    bits,
+                    field: field.name.clone(),
+                    access: if field.negative.unwrap_or(false) { format!("{}, signed", field.access()) } else { field.access().to_string() },
+                    reset: field.reset.map(|reset| format!("{:#x}", reset)).unwrap_or_default(),
+                    description: paragraphs(&field.description, &field.long_description),
+                },
+                None => BitRow {
+                    bits,
+                    field: String::from("Reserved"),
+                    access: String::new(),
+                    reset: String::new(),
+                    description: Vec::new(),
+                },
+            }
+        })
+        .collect()
+}
+
+// Enum values as the field's bits in binary, the way the README lists encodings
+fn encodings(field: &Field) -> Vec<(String, String)> {
+    field.enums
+        .iter()
+        .flatten()
+        .map(|enum_value| {
+            let bits = field.get_value((enum_value.value as u64) << field.lsb);
+            (format!("{:0width$b}b", bits, width = field.width() as usize), enum_value.name.clone())
+        })
+        .collect()
+}
+
+fn register_details(register_family: &RegisterFamily, register: &Register) -> String {
+    let mut details = format!("{}-bit register", register.size);
+    if let Some(offset) = register.offset {
+        details.push_str(&format!(", offset {:#x}", offset));
+    }
+    if let Some(address) = register_family.register_address(register) {
+        details.push_str(&format!(", address {:#x}", address));
+    }
+    details
+}
+
+fn paragraphs(description: &Option<String>, long_description: &Option<String>) -> Vec<String> {
+    description
+        .iter()
+        .chain(long_description)
+        .flat_map(|text| text.split("\n\n"))
+        .map(|paragraph| paragraph.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect()
+}
+
+pub fn generate_markdown(register_family: &RegisterFamily) -> Result<(), io::Error> {
+    write_generated_file(&format!("{}.md", register_family.register_family), &create_markdown_reference(register_family))
+}
+
+pub fn generate_asciidoc(register_family: &RegisterFamily) -> Result<(), io::Error> {
+    write_generated_file(&format!("{}.adoc", register_family.register_family), &create_asciidoc_reference(register_family))
+}
+
+pub fn create_markdown_reference(register_family: &RegisterFamily) -> String {
+    let cell = |text: &str| text.replace('|', "\\|");
+
+    let mut markdown = format!("# {} Registers\n\n", register_family.register_family);
+    for paragraph in paragraphs(&register_family.description, &register_family.long_description) {
+        markdown.push_str(&format!("{}\n\n", paragraph));
+    }
+
+    markdown.push_str("## Contents\n\n");
+    for register in &register_family.registers {
+        let address = register_family
+            .register_address(register)
+            .map(|address| format!(" ({:#x})", address))
+            .unwrap_or_default();
+        markdown.push_str(&format!("- [{}](#{}){}\n", register.name, register.name.to_lowercase(), address));
+    }
+
+    for register in &register_family.registers {
+        markdown.push_str(&format!("\n## {}\n\n", register.name));
+        for paragraph in paragraphs(&register.description, &register.long_description) {
+            markdown.push_str(&format!("{}\n\n", paragraph));
+        }
+        markdown.push_str(&format!("{}\n\n", register_details(register_family, register)));
+
+        markdown.push_str("| Bit Location | Field | Access | Reset | Description |\n| --- | --- | --- | --- | --- |\n");
+        for row in bit_rows(register) {
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                row.bits, cell(&row.field), row.access, row.reset, cell(&row.description.join("<br><br>"))
+            ));
+        }
+
+        for field in &register.fields {
+            let encodings = encodings(field);
+            if encodings.is_empty() {
+                continue;
+            }
+            markdown.push_str(&format!("\nThe encoding for `{}` is:\n\n| Encoding | Meaning |\n| --- | --- |\n", field.name));
+            for (encoding, meaning) in encodings {
+                markdown.push_str(&format!("| {} | {} |\n", encoding, cell(&meaning)));
+            }
+        }
+    }
+    markdown
+}
+
+pub fn create_asciidoc_reference(register_family: &RegisterFamily) -> String {
+    let cell = |text: &str| text.replace('|', "\\|");
+
+    let mut asciidoc = format!("= {} Registers\n:toc:\n\n", register_family.register_family);
+    for paragraph in paragraphs(&register_family.description, &register_family.long_description) {
+        asciidoc.push_str(&format!("{}\n\n", paragraph));
+    }
+
+    for register in &register_family.registers {
+        asciidoc.push_str(&format!("[[{}]]\n== {}\n\n", register.name, register.name));
+        for paragraph in paragraphs(&register.description, &register.long_description) {
+            asciidoc.push_str(&format!("{}\n\n", paragraph));
+        }
+        asciidoc.push_str(&format!("{}\n\n", register_details(register_family, register)));
+
+        asciidoc.push_str("[cols=\"1,2,1,1,4\",options=\"header\"]\n|===\n|Bit Location |Field |Access |Reset |Description\n");
+        for row in bit_rows(register) {
+            asciidoc.push_str(&format!(
+                "|{} |{} |{} |{} |{}\n",
+                row.bits, cell(&row.field), row.access, row.reset, cell(&row.description.join(" +\n"))
+            ));
+        }
+        asciidoc.push_str("|===\n\n");
+
+        for field in &register.fields {
+            let encodings = encodings(field);
+            if encodings.is_empty() {
+                continue;
+            }
+            asciidoc.push_str(&format!(".Encoding of `{}`\n[cols=\"1,3\",options=\"header\"]\n|===\n|Encoding |Meaning\n", field.name));
+            for (encoding, meaning) in encodings {
+                asciidoc.push_str(&format!("|{} |{}\n", encoding, cell(&meaning)));
+            }
+            asciidoc.push_str("|===\n\n");
+        }
+    }
+    asciidoc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family() -> RegisterFamily {
+        RegisterFamily {
+            register_family: String::from("PCIe"),
+            registers: vec![Register {
+                name: String::from("LinkCap"),
+                size: 8,
+                fields: vec![
+                    Field { name: String::from("speed"), lsb: 0, msb: 3, read: true, description: Some(String::from("Max Link Speed")), ..Default::default() },
+                    Field {
+                        name: String::from("aspm"),
+                        lsb: 5,
+                        msb: 6,
+                        read: true,
+                        enums: Some(vec![EnumValue { name: String::from("L0s | L1"), value: 3 }]),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lists_fields_and_reserved_bits_in_markdown() {
+        let markdown = create_markdown_reference(&family());
+        assert!(markdown.contains("\
+            | Bit Location | Field | Access | Reset | Description |\n\
+            | --- | --- | --- | --- | --- |\n\
+            | 7 | Reserved |  |  |  |\n\
+            | 6:5 | aspm | RO |  |  |\n\
+            | 4 | Reserved |  |  |  |\n\
+            | 3:0 | speed | RO |  | Max Link Speed |\n"));
+        assert!(markdown.contains("| 11b | L0s \\| L1 |\n"));
+    }
+
+    #[test]
+    fn lists_fields_and_reserved_bits_in_asciidoc() {
+        let asciidoc = create_asciidoc_reference(&family());
+        assert!(asciidoc.contains("|6:5 |aspm |RO | |\n"));
+        assert!(asciidoc.contains("|3:0 |speed |RO | |Max Link Speed\n"));
+        assert!(asciidoc.contains("|11b |L0s \\| L1\n"));
+    }
+}
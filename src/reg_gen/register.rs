@@ -1,32 +1,117 @@
 use std::io::{self, Write};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Field {
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub long_description: Option<String>,
+    #[schemars(range(max = 63))]
     pub lsb: u8,
+    #[schemars(range(max = 63))]
     pub msb: u8,
     pub read: bool,
     pub write: bool,
-    pub negative: Option<bool>
+    pub negative: Option<bool>,
+    // Raw bits of the field after reset, for documentation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reset: Option<u64>,
+    // Named encodings of the field, left out of the JSON entirely when there are none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enums: Option<Vec<EnumValue>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EnumValue {
+    pub name: String,
+    pub value: i64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Register {
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub long_description: Option<String>,
+    #[schemars(extend("enum" = [8, 16, 32, 64]))]
     pub size: u8,
-    pub fields: Vec<Field>
+    // Byte offset of the register from the family's base address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    pub fields: Vec<Field>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct RegisterFamily {
+    // Lets editors find the schema, see the schema subcommand
+    #[serde(rename = "$schema", default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
     pub register_family: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub long_description: Option<String>,
+    #[schemars(extend("items" = { "enum": [8, 16, 32, 64] }))]
     pub register_family_widths: Vec<u8>,
-    pub registers: Vec<Register>
+    pub registers: Vec<Register>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_address: Option<u64>,
+}
+
+// A run of bits in a register, either a field or bits not covered by any field
+pub struct BitSegment<'a> {
+    pub msb: u8,
+    pub lsb: u8,
+    pub field: Option<&'a Field>,
+}
+
+impl RegisterFamily {
+    // Absolute address of a register, if it has an offset
+    pub fn register_address(&self, register: &Register) -> Option<u64> {
+        register.offset.map(|offset| self.base_address.unwrap_or(0).wrapping_add(offset))
+    }
 }
 
 impl Register {
+    // Splits bits msb down to lsb into fields and the gaps between them, most significant first.
+    // Fields reaching outside the range are cut to it.
+    pub fn bit_segments(&self, msb: u8, lsb: u8) -> Vec<BitSegment<'_>> {
+        let mut fields: Vec<&Field> = self.fields
+            .iter()
+            .filter(|field| field.lsb <= msb && field.msb >= lsb && field.lsb <= field.msb)
+            .collect();
+        fields.sort_by_key(|field| std::cmp::Reverse(field.msb));
+
+        let mut segments = Vec::new();
+        let mut next = msb as i16;
+        for field in fields {
+            let field_msb = field.msb.min(msb) as i16;
+            let field_lsb = field.lsb.max(lsb);
+            // Overlapping fields are rejected by validation, skip them rather than draw nonsense
+            if field_msb > next {
+                continue;
+            }
+            if field_msb < next {
+                segments.push(BitSegment { msb: next as u8, lsb: (field_msb + 1) as u8, field: None });
+            }
+            segments.push(BitSegment { msb: field_msb as u8, lsb: field_lsb, field: Some(field) });
+            next = field_lsb as i16 - 1;
+        }
+        if next >= lsb as i16 {
+            segments.push(BitSegment { msb: next as u8, lsb, field: None });
+        }
+        segments
+    }
+
     pub fn add_register_field(&mut self) {
         print!("Name: ");
         io::stdout().flush().unwrap();
@@ -64,13 +149,85 @@ impl Register {
         io::stdin().read_line(&mut negative).expect("Failed to read line!");
         let negative: bool = negative.trim().parse().expect("Invalid input!");
 
-        self.fields.push(Field {name, lsb, msb, read, write, negative: Some(negative)});
+        self.fields.push(Field {name, lsb, msb, read, write, negative: Some(negative), ..Default::default()});
     }
 }
 
 impl Field {
+    pub fn access(&self) -> &'static str {
+        match (self.read, self.write) {
+            (true, true) => "RW",
+            (true, false) => "RO",
+            (false, true) => "WO",
+            (false, false) => "-",
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        (self.msb as u32 + 1).saturating_sub(self.lsb as u32)
+    }
+
+    fn mask(&self) -> u128 {
+        (1u128 << self.width()) - 1
+    }
+
+    // The bits of the field shifted down, as returned by an unsigned get method
+    pub fn get_value(&self, register_value: u64) -> u64 {
+        ((register_value as u128 >> self.lsb) & self.mask()) as u64
+    }
+
+    // The bits of the field read as two's complement, as returned by a negative get method
+    pub fn get_signed_value(&self, register_value: u64) -> i64 {
+        let value = self.get_value(register_value) as i128;
+        if self.width() > 0 && value & (1 << (self.width() - 1)) != 0 {
+            (value - (1 << self.width())) as i64
+        } else {
+            value as i64
+        }
+    }
+
+    // What the set method does with the value: the register with the field replaced, or None if
+    // the set method would reject the value and return false
+    pub fn set_value(&self, register_value: u64, value: i128) -> Option<u64> {
+        let (min, max) = self.value_range();
+        if value < min || value > max {
+            return None;
+        }
+        let cleared = register_value as u128 & !(self.mask() << self.lsb);
+        Some((cleared | ((value as u128 & self.mask()) << self.lsb)) as u64)
+    }
+
+    // The inclusive range of values the set method accepts
+    pub fn value_range(&self) -> (i128, i128) {
+        let width = self.width();
+        if self.negative.unwrap_or(false) {
+            let half = 1i128 << width.saturating_sub(1);
+            (-half, half - 1)
+        } else {
+            (0, (1i128 << width) - 1)
+        }
+    }
+
+    pub fn enum_name(&self, value: i64) -> Option<&str> {
+        self.enums.as_ref()?.iter().find(|e| e.value == value).map(|e| e.name.as_str())
+    }
+
+    // The value the get method would return, with its enum name if it has one
+    pub fn describe_value(&self, register_value: u64) -> String {
+        let value = if self.negative.unwrap_or(false) {
+            self.get_signed_value(register_value)
+        } else {
+            self.get_value(register_value) as i64
+        };
+        let number = if value < 0 { value.to_string() } else { format!("{:#x}", value) };
+        match self.enum_name(value) {
+            Some(name) => format!("{} ({})", number, name),
+            None => number,
+        }
+    }
+
     pub fn create_get_method(&self, register_width: u8) -> String {
-        return match self.negative {
+        match self.negative {
             Some(true) => format!(
                 "\tinline int{3}_t get_{0}() const {{\n\
                 \t\tuint{3}_t buffer = register_raw >> {1};\n\
@@ -100,7 +257,7 @@ impl Field {
 
     pub fn create_set_method(&self, register_width: u8) -> String {
         // Negative numbers need to be bounds checked differently
-        return match self.negative {
+        match self.negative {
             Some(true) => format!(
                 "\tinline bool set_{0}(int{3}_t value) {{\n\
                 \t\tif (value < 0) {{\n\
@@ -143,3 +300,52 @@ impl Field {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(lsb: u8, msb: u8, negative: bool) -> Field {
+        Field { name: String::from("field"), lsb, msb, read: true, write: true, negative: Some(negative), ..Default::default() }
+    }
+
+    #[test]
+    fn gets_unsigned_and_signed_values() {
+        let field = field(4, 11, true);
+        assert_eq!(field.get_value(0xdeadbeef), 0xee);
+        assert_eq!(field.get_signed_value(0xdeadbeef), -18);
+        assert_eq!(field.get_signed_value(0x00000120), 18);
+    }
+
+    #[test]
+    fn sets_values_within_range_only() {
+        let unsigned = field(0, 3, false);
+        assert_eq!(unsigned.set_value(0xf0, 15), Some(0xff));
+        assert_eq!(unsigned.set_value(0xf0, 16), None);
+        assert_eq!(unsigned.set_value(0xf0, -1), None);
+
+        let signed = field(4, 11, true);
+        assert_eq!(signed.set_value(0xf00f, -1), Some(0xffff));
+        assert_eq!(signed.set_value(0, -128), Some(0x800));
+        assert_eq!(signed.set_value(0, -129), None);
+        assert_eq!(signed.set_value(0, 128), None);
+    }
+
+    #[test]
+    fn splits_registers_into_fields_and_gaps() {
+        let register = Register { name: String::from("R"), size: 16, fields: vec![field(0, 3, false), field(8, 11, false)], ..Default::default() };
+
+        let segments: Vec<(u8, u8, bool)> = register.bit_segments(15, 0).iter().map(|s| (s.msb, s.lsb, s.field.is_some())).collect();
+        assert_eq!(segments, vec![(15, 12, false), (11, 8, true), (7, 4, false), (3, 0, true)]);
+
+        let segments: Vec<(u8, u8, bool)> = register.bit_segments(9, 2).iter().map(|s| (s.msb, s.lsb, s.field.is_some())).collect();
+        assert_eq!(segments, vec![(9, 8, true), (7, 4, false), (3, 2, true)]);
+    }
+
+    #[test]
+    fn handles_full_width_fields() {
+        let field = field(0, 63, false);
+        assert_eq!(field.get_value(u64::MAX), u64::MAX);
+        assert_eq!(field.set_value(0, u64::MAX as i128), Some(u64::MAX));
+    }
+}
@@ -0,0 +1,487 @@
+use std::{collections::HashMap, io, path::Path};
+
+use calamine::{open_workbook_auto, Data, Reader};
+use clap::ValueEnum;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+
+use crate::cli_structs::parse_number;
+use crate::reg_gen::register::*;
+
+// A column of a register map spreadsheet
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, ValueEnum)]
+pub enum Column {
+    Register,
+    Size,
+    Offset,
+    Field,
+    Msb,
+    Lsb,
+    Access,
+    Signed,
+    Reset,
+    Description,
+    LongDescription,
+    Enums,
+}
+
+impl Column {
+    // In the order they are exported
+    pub const ALL: [Column; 12] = [
+        Column::Register,
+        Column::Size,
+        Column::Offset,
+        Column::Field,
+        Column::Msb,
+        Column::Lsb,
+        Column::Access,
+        Column::Signed,
+        Column::Reset,
+        Column::Description,
+        Column::LongDescription,
+        Column::Enums,
+    ];
+
+    // Columns an import can't do without
+    const REQUIRED: [Column; 5] = [Column::Register, Column::Size, Column::Field, Column::Msb, Column::Lsb];
+
+    fn default_header(self) -> &'static str {
+        match self {
+            Column::Register => "register",
+            Column::Size => "size",
+            Column::Offset => "offset",
+            Column::Field => "field",
+            Column::Msb => "msb",
+            Column::Lsb => "lsb",
+            Column::Access => "access",
+            Column::Signed => "signed",
+            Column::Reset => "reset",
+            Column::Description => "description",
+            Column::LongDescription => "long_description",
+            Column::Enums => "enums",
+        }
+    }
+}
+
+// The header of each column, the defaults unless mapped to the names an existing spreadsheet uses
+pub struct ColumnMap {
+    headers: HashMap<Column, String>,
+}
+
+impl ColumnMap {
+    pub fn new(mapping: &[(Column, String)]) -> ColumnMap {
+        let mut headers: HashMap<Column, String> = Column::ALL
+            .iter()
+            .map(|column| (*column, column.default_header().to_string()))
+            .collect();
+        headers.extend(mapping.iter().cloned());
+        ColumnMap { headers }
+    }
+
+    fn header(&self, column: Column) -> &str {
+        &self.headers[&column]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpreadsheetFormat {
+    Csv,
+    Xlsx,
+}
+
+impl SpreadsheetFormat {
+    pub fn of_path(path: &str) -> Result<SpreadsheetFormat, io::Error> {
+        let extension = Path::new(path).extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("csv") => Ok(SpreadsheetFormat::Csv),
+            Some("xlsx") => Ok(SpreadsheetFormat::Xlsx),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Can't tell whether {} is CSV or XLSX from its extension", path))),
+        }
+    }
+}
+
+// One row per register holding its own attributes, followed by a row per field. The register,
+// size and offset are repeated on every field row so the sheet can be sorted and filtered.
+pub fn register_family_to_rows(register_family: &RegisterFamily, columns: &ColumnMap) -> Vec<Vec<String>> {
+    let row = |cells: &[(Column, String)]| -> Vec<String> {
+        Column::ALL
+            .iter()
+            .map(|column| cells.iter().find(|(cell_column, _)| cell_column == column).map(|(_, text)| text.clone()).unwrap_or_default())
+            .collect()
+    };
+
+    let mut rows = vec![Column::ALL.iter().map(|column| columns.header(*column).to_string()).collect()];
+    for register in &register_family.registers {
+        let offset = register.offset.map(|offset| format!("{:#x}", offset)).unwrap_or_default();
+        rows.push(row(&[
+            (Column::Register, register.name.clone()),
+            (Column::Size, register.size.to_string()),
+            (Column::Offset, offset.clone()),
+            (Column::Description, register.description.clone().unwrap_or_default()),
+            (Column::LongDescription, register.long_description.clone().unwrap_or_default()),
+        ]));
+
+        for field in &register.fields {
+            let signed = match field.negative {
+                Some(true) => "yes",
+                Some(false) => "no",
+                None => "",
+            };
+            let enums = field.enums
+                .iter()
+                .flatten()
+                .map(|enum_value| format!("{}={}", enum_value.name, enum_value.value))
+                .collect::<Vec<String>>()
+                .join(", ");
+            rows.push(row(&[
+                (Column::Register, register.name.clone()),
+                (Column::Size, register.size.to_string()),
+                (Column::Offset, offset.clone()),
+                (Column::Field, field.name.clone()),
+                (Column::Msb, field.msb.to_string()),
+                (Column::Lsb, field.lsb.to_string()),
+                (Column::Access, field.access().to_string()),
+                (Column::Signed, signed.to_string()),
+                (Column::Reset, field.reset.map(|reset| format!("{:#x}", reset)).unwrap_or_default()),
+                (Column::Description, field.description.clone().unwrap_or_default()),
+                (Column::LongDescription, field.long_description.clone().unwrap_or_default()),
+                (Column::Enums, enums),
+            ]));
+        }
+    }
+    rows
+}
+
+// Reads the registers back out of a sheet. The first non-empty row is the header, columns are
+// found by name in any order and empty rows are skipped. A row without a field describes the
+// register itself, and field rows with an empty register cell belong to the register above, as
+// when the register cells are merged. Returns every problem found, by spreadsheet row.
+pub fn rows_to_registers(rows: &[Vec<String>], columns: &ColumnMap) -> Result<Vec<Register>, Vec<String>> {
+    let is_empty = |row: &Vec<String>| row.iter().all(|cell| cell.trim().is_empty());
+    let Some(header_row) = rows.iter().position(|row| !is_empty(row)) else {
+        return Err(vec![String::from("The spreadsheet is empty")]);
+    };
+
+    let positions: HashMap<Column, usize> = Column::ALL
+        .iter()
+        .filter_map(|column| {
+            rows[header_row]
+                .iter()
+                .position(|header| header.trim().eq_ignore_ascii_case(columns.header(*column)))
+                .map(|position| (*column, position))
+        })
+        .collect();
+    let missing: Vec<String> = Column::REQUIRED
+        .iter()
+        .filter(|column| !positions.contains_key(column))
+        .map(|column| format!("Missing the `{}` column", columns.header(*column)))
+        .collect();
+    if !missing.is_empty() {
+        return Err(missing);
+    }
+
+    let mut problems = Vec::new();
+    let mut registers: Vec<Register> = Vec::new();
+    let mut sizes: Vec<Option<u8>> = Vec::new();
+    let mut current: Option<usize> = None;
+    for (index, row) in rows.iter().enumerate().skip(header_row + 1) {
+        if is_empty(row) {
+            continue;
+        }
+        let line = index + 1;
+        let cell = |column: Column| -> &str {
+            positions.get(&column).and_then(|position| row.get(*position)).map(|text| text.trim()).unwrap_or("")
+        };
+        let mut problem = |message: String| problems.push(format!("Row {}: {}", line, message));
+
+        let name = cell(Column::Register);
+        if !name.is_empty() {
+            current = Some(match registers.iter().position(|register| register.name == name) {
+                Some(position) => position,
+                None => {
+                    registers.push(Register { name: name.to_string(), ..Default::default() });
+                    sizes.push(None);
+                    registers.len() - 1
+                },
+            });
+        }
+        let Some(position) = current else {
+            problem(String::from("no register given"));
+            continue;
+        };
+        let register = &mut registers[position];
+
+        if !cell(Column::Size).is_empty() {
+            match cell(Column::Size).parse::<u8>() {
+                Ok(size) if sizes[position].is_some_and(|existing| existing != size) => problem(format!(
+                    "register `{}` is {}-bit on an earlier row, not {}-bit", register.name, sizes[position].unwrap(), size
+                )),
+                Ok(size) => sizes[position] = Some(size),
+                Err(_) => problem(format!("size `{}` isn't a number", cell(Column::Size))),
+            }
+        }
+        if !cell(Column::Offset).is_empty() {
+            match parse_unsigned(cell(Column::Offset)) {
+                Ok(offset) if register.offset.is_some_and(|existing| existing != offset) => problem(format!(
+                    "register `{}` is at offset {:#x} on an earlier row, not {:#x}", register.name, register.offset.unwrap(), offset
+                )),
+                Ok(offset) => register.offset = Some(offset),
+                Err(why) => problem(why),
+            }
+        }
+
+        if cell(Column::Field).is_empty() {
+            set_text(&mut register.description, cell(Column::Description));
+            set_text(&mut register.long_description, cell(Column::LongDescription));
+            continue;
+        }
+
+        match row_to_field(&cell) {
+            Ok(field) => register.fields.push(field),
+            Err(why) => problem(why),
+        }
+    }
+
+    for (register, size) in registers.iter_mut().zip(sizes) {
+        match size {
+            Some(size) => register.size = size,
+            None => problems.push(format!("Register `{}` has no size", register.name)),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(registers)
+    } else {
+        Err(problems)
+    }
+}
+
+fn row_to_field<'a>(cell: &dyn Fn(Column) -> &'a str) -> Result<Field, String> {
+    let bit = |column: Column| -> Result<u8, String> {
+        cell(column).parse::<u8>().map_err(|_| format!("{} `{}` isn't a bit number", column.default_header(), cell(column)))
+    };
+    let name = cell(Column::Field);
+
+    // Blank access is read-write, the most common case
+    let (read, write) = match cell(Column::Access).to_uppercase().replace(['/', ' '], "").as_str() {
+        "" | "RW" => (true, true),
+        "RO" | "R" => (true, false),
+        "WO" | "W" => (false, true),
+        "-" => (false, false),
+        other => return Err(format!("field `{}` has unknown access `{}`, expected RW, RO, WO or -", name, other)),
+    };
+    let negative = match cell(Column::Signed).to_lowercase().as_str() {
+        "" => None,
+        "yes" | "y" | "true" | "1" | "signed" => Some(true),
+        "no" | "n" | "false" | "0" | "unsigned" => Some(false),
+        other => return Err(format!("field `{}` has signed `{}`, expected yes or no", name, other)),
+    };
+    let reset = match cell(Column::Reset) {
+        "" => None,
+        reset => Some(parse_unsigned(reset)?),
+    };
+    let enums = match cell(Column::Enums) {
+        "" => None,
+        enums => Some(
+            enums
+                .split([',', '\n'])
+                .filter(|entry| !entry.trim().is_empty())
+                .map(|entry| {
+                    let (enum_name, value) = entry
+                        .split_once('=')
+                        .ok_or(format!("field `{}` has enum `{}`, expected name=value", name, entry.trim()))?;
+                    let value = parse_number(value)?;
+                    Ok(EnumValue {
+                        name: enum_name.trim().to_string(),
+                        value: i64::try_from(value).map_err(|_| format!("enum value {} is out of range", value))?,
+                    })
+                })
+                .collect::<Result<Vec<EnumValue>, String>>()?,
+        ),
+    };
+
+    let mut field = Field {
+        name: name.to_string(),
+        lsb: bit(Column::Lsb)?,
+        msb: bit(Column::Msb)?,
+        read,
+        write,
+        negative,
+        reset,
+        enums,
+        ..Default::default()
+    };
+    set_text(&mut field.description, cell(Column::Description));
+    set_text(&mut field.long_description, cell(Column::LongDescription));
+    Ok(field)
+}
+
+fn parse_unsigned(text: &str) -> Result<u64, String> {
+    u64::try_from(parse_number(text)?).map_err(|_| format!("`{}` isn't a positive 64-bit number", text))
+}
+
+fn set_text(target: &mut Option<String>, text: &str) {
+    if !text.is_empty() {
+        *target = Some(text.to_string());
+    }
+}
+
+// The first worksheet of an XLSX file is read unless another is named
+pub fn read_spreadsheet(path: &str, sheet: Option<&str>) -> Result<Vec<Vec<String>>, io::Error> {
+    let couldnt_read = |why: String| io::Error::new(io::ErrorKind::InvalidData, format!("Couldn't read {}: {}", path, why));
+    match SpreadsheetFormat::of_path(path)? {
+        SpreadsheetFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_path(path)
+                .map_err(|why| couldnt_read(why.to_string()))?;
+            reader
+                .records()
+                .map(|record| record.map(|record| record.iter().map(String::from).collect()))
+                .collect::<Result<_, _>>()
+                .map_err(|why| couldnt_read(why.to_string()))
+        },
+        SpreadsheetFormat::Xlsx => {
+            let mut workbook = open_workbook_auto(path).map_err(|why| couldnt_read(why.to_string()))?;
+            let range = match sheet {
+                Some(sheet) => workbook.worksheet_range(sheet).map_err(|why| couldnt_read(why.to_string()))?,
+                None => workbook
+                    .worksheet_range_at(0)
+                    .ok_or(couldnt_read(String::from("it has no worksheets")))?
+                    .map_err(|why| couldnt_read(why.to_string()))?,
+            };
+            Ok(range.rows().map(|row| row.iter().map(cell_text).collect()).collect())
+        },
+    }
+}
+
+// Spreadsheet programs store bit numbers and sizes as floats
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::String(text) => text.clone(),
+        Data::Float(number) if number.fract() == 0.0 => format!("{}", *number as i64),
+        other => other.to_string(),
+    }
+}
+
+pub fn write_spreadsheet(path: &str, rows: &[Vec<String>]) -> Result<(), io::Error> {
+    match SpreadsheetFormat::of_path(path)? {
+        SpreadsheetFormat::Csv => {
+            let couldnt_write = |why: csv::Error| io::Error::other(format!("Couldn't write to {}: {}", path, why));
+            let mut writer = csv::Writer::from_path(path).map_err(couldnt_write)?;
+            for row in rows {
+                writer.write_record(row).map_err(couldnt_write)?;
+            }
+            writer.flush()
+        },
+        SpreadsheetFormat::Xlsx => write_xlsx(path, rows)
+            .map_err(|why| io::Error::other(format!("Couldn't write to {}: {}", path, why))),
+    }
+}
+
+// Numbers are written as numbers so the sheet sorts properly, with a bold header frozen at the top
+fn write_xlsx(path: &str, rows: &[Vec<String>]) -> Result<(), XlsxError> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    let bold = Format::new().set_bold();
+    for (row_index, row) in rows.iter().enumerate() {
+        for (column_index, text) in row.iter().enumerate() {
+            let (row_index, column_index) = (row_index as u32, column_index as u16);
+            if row_index == 0 {
+                worksheet.write_string_with_format(row_index, column_index, text, &bold)?;
+            } else if let Ok(number) = text.parse::<u32>() {
+                worksheet.write_number(row_index, column_index, number)?;
+            } else {
+                worksheet.write_string(row_index, column_index, text)?;
+            }
+        }
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+    worksheet.autofit();
+    workbook.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers() -> Vec<Register> {
+        vec![
+            Register {
+                name: String::from("CTRL"),
+                description: Some(String::from("Control, with a \"quoted\" word")),
+                size: 32,
+                offset: Some(0x10),
+                fields: vec![
+                    Field { name: String::from("en"), lsb: 0, msb: 0, read: true, write: true, reset: Some(1), ..Default::default() },
+                    Field {
+                        name: String::from("mode"),
+                        long_description: Some(String::from("First line\n\nSecond paragraph")),
+                        lsb: 4,
+                        msb: 7,
+                        read: true,
+                        negative: Some(false),
+                        enums: Some(vec![EnumValue { name: String::from("OFF"), value: 0 }, EnumValue { name: String::from("ON"), value: 1 }]),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            Register { name: String::from("EMPTY"), size: 8, ..Default::default() },
+        ]
+    }
+
+    #[test]
+    fn round_trips_registers_through_rows() {
+        let register_family = RegisterFamily { register_family: String::from("Test"), registers: registers(), ..Default::default() };
+        let columns = ColumnMap::new(&[(Column::Field, String::from("Bit Field"))]);
+        let rows = register_family_to_rows(&register_family, &columns);
+        assert_eq!(rows[0][3], "Bit Field");
+        assert_eq!(rows[3], ["CTRL", "32", "0x10", "mode", "7", "4", "RO", "no", "", "", "First line\n\nSecond paragraph", "OFF=0, ON=1"]);
+
+        let imported = rows_to_registers(&rows, &columns).unwrap();
+        assert_eq!(serde_json::to_value(imported).unwrap(), serde_json::to_value(registers()).unwrap());
+    }
+
+    #[test]
+    fn reads_architect_style_sheets() {
+        let rows: Vec<Vec<String>> = [
+            vec!["", "", "", "", ""],
+            vec!["Field", "Register", "Bits 0", "MSB", "Width"],
+            vec!["en", "CTRL", "0", "0", "16"],
+            vec!["mode", "", "4", "7", ""],
+            vec!["", "", "", "", ""],
+            vec!["ready", "STAT", "0", "0", "16"],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+        let columns = ColumnMap::new(&[(Column::Lsb, String::from("bits 0")), (Column::Size, String::from("width"))]);
+
+        let registers = rows_to_registers(&rows, &columns).unwrap();
+        assert_eq!(registers.len(), 2);
+        assert_eq!(registers[0].fields.iter().map(|field| field.name.as_str()).collect::<Vec<&str>>(), ["en", "mode"]);
+        assert!(registers[0].fields[1].read && registers[0].fields[1].write);
+        assert_eq!(registers[1].size, 16);
+    }
+
+    #[test]
+    fn reports_problems_by_row() {
+        let rows: Vec<Vec<String>> = [
+            vec!["register", "size", "field", "msb", "lsb", "access"],
+            vec!["CTRL", "32", "en", "0", "0", "RW"],
+            vec!["CTRL", "16", "mode", "seven", "4", "RC"],
+            vec!["STAT", "", "", "", "", ""],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+
+        assert_eq!(rows_to_registers(&rows, &ColumnMap::new(&[])).unwrap_err(), [
+            "Row 3: register `CTRL` is 32-bit on an earlier row, not 16-bit",
+            "Row 3: field `mode` has unknown access `RC`, expected RW, RO, WO or -",
+            "Register `STAT` has no size",
+        ]);
+    }
+}
@@ -0,0 +1,28 @@
+// Formats rows as plain text columns padded to the widest cell, with a rule under the headers
+pub fn format_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| -> String {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        line.join("  ").trim_end().to_string()
+    };
+
+    let mut table = format_row(headers.to_vec());
+    table.push('\n');
+    table.push_str(&format_row(widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<String>>().iter().map(String::as_str).collect()));
+    table.push('\n');
+    for row in rows {
+        table.push_str(&format_row(row.iter().map(String::as_str).collect()));
+        table.push('\n');
+    }
+    table
+}
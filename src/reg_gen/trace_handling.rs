@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::cli_structs::parse_number;
+use crate::reg_gen::register::*;
+
+// Decodes every line of the log matched by the pattern as a register write. The first write to a
+// register lists all of its fields, later writes only list the fields that changed since the
+// previous write to the same register. Returns the registers that share an address instead, as
+// writes to them couldn't be told apart.
+pub fn trace_log(families: &[RegisterFamily], log: &str, pattern: &Regex) -> Result<String, Vec<String>> {
+    let mut registers_by_address: HashMap<u64, (&RegisterFamily, &Register)> = HashMap::new();
+    let mut clashes = Vec::new();
+    for family in families {
+        for register in &family.registers {
+            let Some(address) = family.register_address(register) else {
+                continue;
+            };
+            if let Some((other_family, other)) = registers_by_address.insert(address, (family, register)) {
+                clashes.push(format!(
+                    "{}.{} and {}.{} are both at {:#x}",
+                    other_family.register_family, other.name, family.register_family, register.name, address
+                ));
+            }
+        }
+    }
+    if !clashes.is_empty() {
+        return Err(clashes);
+    }
+
+    let mut last_values: HashMap<u64, u64> = HashMap::new();
+    let mut trace = String::new();
+    for (line_number, line) in log.lines().enumerate() {
+        let Some(captures) = pattern.captures(line) else {
+            continue;
+        };
+        let line_number = line_number + 1;
+
+        // Negative or over 64-bit numbers would wrap, e.g. 0x1_0000_0000_4000_0004 onto 0x4000_0004
+        let number = |group: &str| captures.name(group).and_then(|m| parse_number(m.as_str()).ok()).and_then(|number| u64::try_from(number).ok());
+        let (address, value) = match (number("address"), number("value")) {
+            (Some(address), Some(value)) => (address, value),
+            _ => {
+                trace.push_str(&format!("{}: couldn't read an address and value from `{}`\n", line_number, line.trim()));
+                continue;
+            },
+        };
+
+        let Some((_, register)) = registers_by_address.get(&address) else {
+            trace.push_str(&format!("{}: {:#x} = {:#x} (no register at this address)\n", line_number, address, value));
+            continue;
+        };
+
+        trace.push_str(&format!("{}: {} ({:#x}) = {:#x}\n", line_number, register.name, address, value));
+        let previous = last_values.insert(address, value);
+        for field in &register.fields {
+            match previous {
+                None => trace.push_str(&format!("    {}: {}\n", field.name, field.describe_value(value))),
+                Some(previous) if field.get_value(previous) != field.get_value(value) => {
+                    trace.push_str(&format!("    {}: {} -> {}\n", field.name, field.describe_value(previous), field.describe_value(value)));
+                },
+                Some(_) => {},
+            }
+        }
+    }
+    Ok(trace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli_structs::DEFAULT_TRACE_PATTERN;
+
+    fn family() -> RegisterFamily {
+        RegisterFamily {
+            register_family: String::from("Test"),
+            register_family_widths: vec![32],
+            registers: vec![Register {
+                name: String::from("CTRL"),
+                size: 32,
+                fields: vec![
+                    Field { name: String::from("en"), lsb: 0, msb: 0, read: true, write: true, ..Default::default() },
+                    Field { name: String::from("mode"), lsb: 1, msb: 3, read: true, write: true, ..Default::default() },
+                ],
+                offset: Some(0x4),
+                ..Default::default()
+            }],
+            base_address: Some(0x4000_0000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reports_field_changes_between_writes() {
+        let family = family();
+        let log = "W 0x4000_0004 = 0x1\nW 0x4000_0004 = 0x5\nW 0x4000_0008 = 0x1\n";
+
+        let trace = trace_log(&[family], log, &Regex::new(DEFAULT_TRACE_PATTERN).unwrap()).unwrap();
+        assert_eq!(trace, "\
+            1: CTRL (0x40000004) = 0x1\n    en: 0x1\n    mode: 0x0\n\
+            2: CTRL (0x40000004) = 0x5\n    mode: 0x0 -> 0x2\n\
+            3: 0x40000008 = 0x1 (no register at this address)\n");
+    }
+
+    #[test]
+    fn refuses_numbers_outside_64_bits() {
+        let pattern = Regex::new(r"W (?P<address>-?\w+) = (?P<value>-?\w+)").unwrap();
+        let log = "W -0x4000_0004 = 0x1\nW 0x4000_0004 = 0x1_0000_0000_0000_0001\nW 0x1_0000_0000_4000_0004 = 0x1\n";
+        assert_eq!(trace_log(&[family()], log, &pattern).unwrap(), "\
+            1: couldn't read an address and value from `W -0x4000_0004 = 0x1`\n\
+            2: couldn't read an address and value from `W 0x4000_0004 = 0x1_0000_0000_0000_0001`\n\
+            3: couldn't read an address and value from `W 0x1_0000_0000_4000_0004 = 0x1`\n");
+    }
+
+    #[test]
+    fn refuses_families_sharing_an_address() {
+        let mut other = family();
+        other.register_family = String::from("Other");
+        other.registers[0].name = String::from("MODE");
+        assert_eq!(trace_log(&[family(), other], "", &Regex::new(DEFAULT_TRACE_PATTERN).unwrap()).unwrap_err(), ["Test.CTRL and Other.MODE are both at 0x40000004"]);
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::reg_gen::register::*;
+
+pub const SUPPORTED_REGISTER_WIDTHS: [u8; 4] = [8, 16, 32, 64];
+
+// Checks everything the header generator relies on, so that commands modifying a configuration
+// can refuse to write one that would generate broken C++. Returns every problem found rather than
+// stopping at the first.
+pub fn validate_register_family(register_family: &RegisterFamily) -> Result<(), Vec<String>> {
+    let mut problems = Vec::new();
+
+    if register_family.register_family.trim().is_empty() {
+        problems.push(String::from("The register family name is empty"));
+    }
+
+    let mut register_names = HashSet::new();
+    let mut register_offsets = HashMap::new();
+    for register in &register_family.registers {
+        if let Some(offset) = register.offset {
+            if let Some(other) = register_offsets.insert(offset, &register.name) {
+                problems.push(format!("Register `{}` has the same offset {:#x} as register `{}`", register.name, offset, other));
+            }
+        }
+        if !register_names.insert(&register.name) {
+            problems.push(format!("Register `{}` is defined more than once", register.name));
+        }
+        if !register_family.register_family_widths.contains(&register.size) {
+            problems.push(format!("Register `{}` is {}-bit, which is missing from register_family_widths", register.name, register.size));
+        }
+        validate_register(register, &mut problems);
+    }
+
+    for width in &register_family.register_family_widths {
+        if !SUPPORTED_REGISTER_WIDTHS.contains(width) {
+            problems.push(format!("Unsupported register family width {}", width));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+// TOML integers are signed, so larger values can only be written to JSON and YAML
+pub fn validate_toml_integers(register_family: &RegisterFamily) -> Vec<String> {
+    let too_large = |value: Option<u64>| value.filter(|value| i64::try_from(*value).is_err());
+    let mut problems = Vec::new();
+    if let Some(base_address) = too_large(register_family.base_address) {
+        problems.push(format!("The base address {:#x} is too large for TOML", base_address));
+    }
+    for register in &register_family.registers {
+        if let Some(offset) = too_large(register.offset) {
+            problems.push(format!("Register `{}` has offset {:#x}, too large for TOML", register.name, offset));
+        }
+        for field in &register.fields {
+            if let Some(reset) = too_large(field.reset) {
+                problems.push(format!("Field `{}.{}` has reset value {:#x}, too large for TOML", register.name, field.name, reset));
+            }
+        }
+    }
+    problems
+}
+
+fn validate_register(register: &Register, problems: &mut Vec<String>) {
+    // The register name becomes a class name
+    if !is_identifier(&register.name) {
+        problems.push(format!("Register name `{}` is not a valid C++ identifier", register.name));
+    }
+    if !SUPPORTED_REGISTER_WIDTHS.contains(&register.size) {
+        problems.push(format!("Register `{}` has unsupported size {}", register.name, register.size));
+    }
+
+    let mut field_names = HashSet::new();
+    for (i, field) in register.fields.iter().enumerate() {
+        let path = format!("{}.{}", register.name, field.name);
+
+        // The field name becomes the suffix of get_ and set_ methods
+        if field.name.is_empty() || !field.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            problems.push(format!("Field name `{}` is not valid in a C++ method name", path));
+        }
+        if !field_names.insert(&field.name) {
+            problems.push(format!("Field `{}` is defined more than once", path));
+        }
+        if field.lsb > field.msb {
+            problems.push(format!("Field `{}` has lsb {} above msb {}", path, field.lsb, field.msb));
+        }
+        if let Some(reset) = field.reset {
+            if field.lsb <= field.msb && reset > field.get_value(u64::MAX) {
+                problems.push(format!("Field `{}` has reset value {:#x} wider than its {} bits", path, reset, field.width()));
+            }
+        }
+        if field.msb >= register.size {
+            problems.push(format!("Field `{}` has msb {} outside of a {}-bit register", path, field.msb, register.size));
+        }
+
+        for other in &register.fields[..i] {
+            if field.lsb <= other.msb && other.lsb <= field.msb {
+                problems.push(format!("Field `{}` overlaps field `{}.{}`", path, register.name, other.name));
+            }
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, lsb: u8, msb: u8) -> Field {
+        Field { name: String::from(name), lsb, msb, read: true, write: true, ..Default::default() }
+    }
+
+    fn family() -> RegisterFamily {
+        RegisterFamily {
+            register_family: String::from("Hif"),
+            register_family_widths: vec![8, 32],
+            registers: vec![
+                Register { name: String::from("CTRL"), size: 32, offset: Some(0x0), fields: vec![field("en", 0, 0), field("mode", 4, 5)], ..Default::default() },
+                Register { name: String::from("STATUS"), size: 8, offset: Some(0x4), fields: vec![field("ready", 0, 0)], ..Default::default() },
+            ],
+            ..Default::default()
+        }
+    }
+
+    // The problems found after changing a valid family
+    fn problems(change: impl FnOnce(&mut RegisterFamily)) -> Vec<String> {
+        let mut register_family = family();
+        change(&mut register_family);
+        validate_register_family(&register_family).unwrap_err()
+    }
+
+    #[test]
+    fn accepts_a_valid_family() {
+        assert_eq!(validate_register_family(&family()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_an_empty_family_name() {
+        assert_eq!(problems(|family| family.register_family = String::from(" ")), ["The register family name is empty"]);
+    }
+
+    #[test]
+    fn rejects_duplicate_register_names_and_offsets() {
+        assert_eq!(problems(|family| family.registers[1].name = String::from("CTRL")), ["Register `CTRL` is defined more than once"]);
+        assert_eq!(problems(|family| family.registers[1].offset = Some(0x0)), ["Register `STATUS` has the same offset 0x0 as register `CTRL`"]);
+    }
+
+    #[test]
+    fn rejects_unsupported_and_undeclared_widths() {
+        assert_eq!(
+            problems(|family| family.registers[1].size = 24),
+            ["Register `STATUS` is 24-bit, which is missing from register_family_widths", "Register `STATUS` has unsupported size 24"]
+        );
+        assert_eq!(problems(|family| family.register_family_widths.push(12)), ["Unsupported register family width 12"]);
+        assert_eq!(problems(|family| family.register_family_widths = vec![32]), ["Register `STATUS` is 8-bit, which is missing from register_family_widths"]);
+    }
+
+    #[test]
+    fn rejects_invalid_identifiers() {
+        assert_eq!(problems(|family| family.registers[0].name = String::from("2CTRL")), ["Register name `2CTRL` is not a valid C++ identifier"]);
+        assert_eq!(problems(|family| family.registers[0].fields[0].name = String::from("en-able")), ["Field name `CTRL.en-able` is not valid in a C++ method name"]);
+        assert_eq!(problems(|family| family.registers[0].fields[1].name = String::from("en")), ["Field `CTRL.en` is defined more than once"]);
+    }
+
+    #[test]
+    fn rejects_fields_that_dont_fit() {
+        assert_eq!(problems(|family| family.registers[0].fields[1].lsb = 6), ["Field `CTRL.mode` has lsb 6 above msb 5"]);
+        assert_eq!(problems(|family| family.registers[1].fields[0].msb = 8), ["Field `STATUS.ready` has msb 8 outside of a 8-bit register"]);
+        assert_eq!(problems(|family| family.registers[0].fields[1].lsb = 0), ["Field `CTRL.mode` overlaps field `CTRL.en`"]);
+    }
+
+    #[test]
+    fn rejects_reset_values_wider_than_their_field() {
+        assert_eq!(problems(|family| family.registers[0].fields[1].reset = Some(0x4)), ["Field `CTRL.mode` has reset value 0x4 wider than its 2 bits"]);
+        let mut register_family = family();
+        register_family.registers[0].fields[1].reset = Some(0x3);
+        assert_eq!(validate_register_family(&register_family), Ok(()));
+    }
+
+    #[test]
+    fn rejects_integers_too_large_for_toml() {
+        let mut register_family = family();
+        register_family.base_address = Some(0x8000_0000_0000_0000);
+        register_family.registers[0].offset = Some(u64::MAX);
+        register_family.registers[1].fields[0].reset = Some(0x7fff_ffff_ffff_ffff);
+        assert_eq!(
+            validate_toml_integers(&register_family),
+            ["The base address 0x8000000000000000 is too large for TOML", "Register `CTRL` has offset 0xffffffffffffffff, too large for TOML"]
+        );
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let problems = problems(|family| {
+            family.register_family.clear();
+            family.registers[0].fields[0].lsb = 1;
+            family.registers[1].offset = Some(0x0);
+        });
+        assert_eq!(problems.len(), 3);
+    }
+}
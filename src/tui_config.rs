@@ -0,0 +1,473 @@
+use std::{collections::HashMap, env, fs, io, path::PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use tui::style::Color;
+
+// Keybindings and colours for the TUI. Both start from a built-in preset and can be overridden
+// per action or per colour from a JSON config file at
+// $XDG_CONFIG_HOME/register_generator/config.json (or ~/.config/register_generator/config.json):
+//
+// {
+//   "preset": "vim",
+//   "keys": { "save": ["s", "Ctrl-s"] },
+//   "theme": { "highlight": "#ff8800" }
+// }
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    QuitWithoutSaving,
+    Save,
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+    TogglePreview,
+    RegisterList,
+    Search,
+    CopyRegister,
+    MoveRegister,
+    Help,
+}
+
+impl Action {
+    // In the order they are listed in the help overlay
+    pub const ALL: [Action; 15] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Select,
+        Action::Back,
+        Action::TogglePreview,
+        Action::RegisterList,
+        Action::Search,
+        Action::CopyRegister,
+        Action::MoveRegister,
+        Action::Save,
+        Action::Quit,
+        Action::QuitWithoutSaving,
+        Action::Help,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Save all modified families and quit",
+            Action::QuitWithoutSaving => "Quit, discarding unsaved edits",
+            Action::Save => "Save the current family",
+            Action::Up => "Previous field or field info",
+            Action::Down => "Next field or field info",
+            Action::Left => "Previous register or family",
+            Action::Right => "Next register or family",
+            Action::Select => "Go down a level or edit",
+            Action::Back => "Go up a level",
+            Action::TogglePreview => "Toggle the generated code preview",
+            Action::RegisterList => "Open the filterable register list",
+            Action::Search => "Search registers and fields",
+            Action::CopyRegister => "Copy the register to another family",
+            Action::MoveRegister => "Move the register to another family",
+            Action::Help => "Show this help",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    // Parses names like "q", "?", "Enter", "Left", "Ctrl-s" or "Alt-Up"
+    pub fn parse(text: &str) -> Result<KeyBinding, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        loop {
+            if let Some(stripped) = rest.strip_prefix("Ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = stripped;
+            } else if let Some(stripped) = rest.strip_prefix("Alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = stripped;
+            } else {
+                break;
+            }
+        }
+
+        let code = match rest {
+            "Enter" => KeyCode::Enter,
+            "Esc" => KeyCode::Esc,
+            "Backspace" => KeyCode::Backspace,
+            "Tab" => KeyCode::Tab,
+            "Space" => KeyCode::Char(' '),
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => KeyCode::Char(ch),
+                    _ => return Err(format!("Unknown key `{}`", text)),
+                }
+            }
+        };
+        Ok(KeyBinding { code, modifiers })
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        // Shift is already part of the character for printable keys, so it is ignored
+        self.code == key.code && self.modifiers == key.modifiers.difference(KeyModifiers::SHIFT)
+    }
+
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            name.push_str("Ctrl-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            name.push_str("Alt-");
+        }
+        match self.code {
+            KeyCode::Char(' ') => name.push_str("Space"),
+            KeyCode::Char(ch) => name.push(ch),
+            KeyCode::Enter => name.push_str("Enter"),
+            KeyCode::Esc => name.push_str("Esc"),
+            KeyCode::Backspace => name.push_str("Backspace"),
+            KeyCode::Tab => name.push_str("Tab"),
+            KeyCode::Up => name.push_str("Up"),
+            KeyCode::Down => name.push_str("Down"),
+            KeyCode::Left => name.push_str("Left"),
+            KeyCode::Right => name.push_str("Right"),
+            KeyCode::Home => name.push_str("Home"),
+            KeyCode::End => name.push_str("End"),
+            KeyCode::PageUp => name.push_str("PageUp"),
+            KeyCode::PageDown => name.push_str("PageDown"),
+            _ => name.push('?'),
+        }
+        name
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: HashMap<Action, Vec<KeyBinding>>,
+}
+
+impl KeyMap {
+    pub fn preset(name: &str) -> Option<KeyMap> {
+        let bindings: &[(Action, &[&str])] = match name {
+            "default" | "high-contrast" => &[
+                (Action::Quit, &["q"]),
+                (Action::QuitWithoutSaving, &["Q"]),
+                (Action::Save, &["s"]),
+                (Action::Up, &["Up"]),
+                (Action::Down, &["Down"]),
+                (Action::Left, &["Left"]),
+                (Action::Right, &["Right"]),
+                (Action::Select, &["Enter"]),
+                (Action::Back, &["Esc"]),
+                (Action::TogglePreview, &["p"]),
+                (Action::RegisterList, &["l"]),
+                (Action::Search, &["/"]),
+                (Action::CopyRegister, &["c"]),
+                (Action::MoveRegister, &["m"]),
+                (Action::Help, &["?"]),
+            ],
+            "vim" => &[
+                (Action::Quit, &["q"]),
+                (Action::QuitWithoutSaving, &["Q"]),
+                (Action::Save, &["w"]),
+                (Action::Up, &["k", "Up"]),
+                (Action::Down, &["j", "Down"]),
+                (Action::Left, &["h", "Left"]),
+                (Action::Right, &["l", "Right"]),
+                (Action::Select, &["Enter"]),
+                (Action::Back, &["Esc"]),
+                (Action::TogglePreview, &["p"]),
+                (Action::RegisterList, &["b"]),
+                (Action::Search, &["/"]),
+                (Action::CopyRegister, &["y"]),
+                (Action::MoveRegister, &["d"]),
+                (Action::Help, &["?"]),
+            ],
+            _ => return None,
+        };
+
+        let bindings = bindings
+            .iter()
+            .map(|(action, keys)| (*action, keys.iter().map(|key| KeyBinding::parse(key).unwrap()).collect()))
+            .collect();
+        Some(KeyMap { bindings })
+    }
+
+    pub fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|action| self.keys(*action).iter().any(|binding| binding.matches(key)))
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyBinding] {
+        self.bindings.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    // Keys bound to more than one action, which action_for would silently resolve to the first
+    fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (i, action) in Action::ALL.iter().enumerate() {
+            for other in &Action::ALL[i + 1..] {
+                for binding in self.keys(*action).iter().filter(|binding| self.keys(*other).contains(binding)) {
+                    conflicts.push(format!("`{}` is bound to both \"{}\" and \"{}\"", binding.name(), action.description(), other.description()));
+                }
+            }
+        }
+        conflicts
+    }
+
+    // All keys bound to an action joined for display, e.g. "k/Up"
+    pub fn describe(&self, action: Action) -> String {
+        self.keys(action).iter().map(KeyBinding::name).collect::<Vec<String>>().join("/")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub background: Color,
+    pub foreground: Color,
+    /// Background of the selection in the list that currently has focus
+    pub highlight: Color,
+    pub highlight_foreground: Color,
+    /// Background of the selection in lists that don't have focus
+    pub inactive_highlight: Color,
+    /// Background of text being typed into the edit popup
+    pub input: Color,
+    pub status: Color,
+    /// Hints and scroll indicators
+    pub muted: Color,
+}
+
+impl Theme {
+    pub fn preset(name: &str) -> Option<Theme> {
+        match name {
+            "default" | "vim" => Some(Theme {
+                background: Color::Rgb(40, 44, 52),
+                foreground: Color::White,
+                highlight: Color::LightMagenta,
+                highlight_foreground: Color::White,
+                inactive_highlight: Color::DarkGray,
+                input: Color::Magenta,
+                status: Color::Yellow,
+                muted: Color::DarkGray,
+            }),
+            "high-contrast" => Some(Theme {
+                background: Color::Black,
+                foreground: Color::White,
+                highlight: Color::Yellow,
+                highlight_foreground: Color::Black,
+                inactive_highlight: Color::Blue,
+                input: Color::Blue,
+                status: Color::LightCyan,
+                muted: Color::Gray,
+            }),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, name: &str, color: Color) -> Result<(), String> {
+        let slot = match name {
+            "background" => &mut self.background,
+            "foreground" => &mut self.foreground,
+            "highlight" => &mut self.highlight,
+            "highlight_foreground" => &mut self.highlight_foreground,
+            "inactive_highlight" => &mut self.inactive_highlight,
+            "input" => &mut self.input,
+            "status" => &mut self.status,
+            "muted" => &mut self.muted,
+            _ => return Err(format!("Unknown theme colour `{}`", name)),
+        };
+        *slot = color;
+        Ok(())
+    }
+}
+
+// Accepts "#rrggbb" or one of the terminal colour names, e.g. "LightMagenta"
+fn parse_color(text: &str) -> Result<Color, String> {
+    if let Some(hex) = text.strip_prefix('#') {
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|channel| u8::from_str_radix(channel, 16).ok());
+        return match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::Rgb(r, g, b)),
+            _ => Err(format!("Invalid colour `{}`", text)),
+        };
+    }
+
+    Ok(match text {
+        "Reset" => Color::Reset,
+        "Black" => Color::Black,
+        "Red" => Color::Red,
+        "Green" => Color::Green,
+        "Yellow" => Color::Yellow,
+        "Blue" => Color::Blue,
+        "Magenta" => Color::Magenta,
+        "Cyan" => Color::Cyan,
+        "Gray" => Color::Gray,
+        "DarkGray" => Color::DarkGray,
+        "LightRed" => Color::LightRed,
+        "LightGreen" => Color::LightGreen,
+        "LightYellow" => Color::LightYellow,
+        "LightBlue" => Color::LightBlue,
+        "LightMagenta" => Color::LightMagenta,
+        "LightCyan" => Color::LightCyan,
+        "White" => Color::White,
+        _ => return Err(format!("Invalid colour `{}`", text)),
+    })
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    preset: Option<String>,
+    #[serde(default)]
+    keys: HashMap<Action, Vec<String>>,
+    #[serde(default)]
+    theme: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TuiConfig {
+    pub keymap: KeyMap,
+    pub theme: Theme,
+}
+
+impl Default for TuiConfig {
+    fn default() -> TuiConfig {
+        TuiConfig::preset("default").unwrap()
+    }
+}
+
+impl TuiConfig {
+    pub fn preset(name: &str) -> Option<TuiConfig> {
+        Some(TuiConfig {
+            keymap: KeyMap::preset(name)?,
+            theme: Theme::preset(name)?,
+        })
+    }
+
+    // Loads the config file if there is one, otherwise the default preset. A preset given on the
+    // command line replaces the one in the file, but the file's overrides still apply on top.
+    pub fn load(preset: Option<&str>) -> Result<TuiConfig, io::Error> {
+        let file = match config_path() {
+            Some(path) if path.exists() => {
+                let contents = fs::read_to_string(&path)?;
+                serde_json::from_str(&contents).map_err(|why| invalid(format!("Couldn't read {}: {}", path.display(), why)))?
+            },
+            _ => ConfigFile::default(),
+        };
+        TuiConfig::from_file(file, preset)
+    }
+
+    fn from_file(file: ConfigFile, preset: Option<&str>) -> Result<TuiConfig, io::Error> {
+        let preset = preset.map(String::from).or(file.preset).unwrap_or(String::from("default"));
+        let mut config = TuiConfig::preset(&preset).ok_or(invalid(format!("Unknown preset `{}`, expected default, vim or high-contrast", preset)))?;
+
+        for (action, keys) in file.keys {
+            let keys = keys.iter().map(|key| KeyBinding::parse(key)).collect::<Result<Vec<KeyBinding>, String>>().map_err(invalid)?;
+            config.keymap.bindings.insert(action, keys);
+        }
+        for (name, color) in file.theme {
+            config.theme.set(&name, parse_color(&color).map_err(invalid)?).map_err(invalid)?;
+        }
+
+        let conflicts = config.keymap.conflicts();
+        if !conflicts.is_empty() {
+            return Err(invalid(format!("Conflicting keybindings:\n  {}", conflicts.join("\n  "))));
+        }
+        Ok(config)
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("register_generator").join("config.json"))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: &str, preset: Option<&str>) -> Result<TuiConfig, io::Error> {
+        TuiConfig::from_file(serde_json::from_str(json).unwrap(), preset)
+    }
+
+    #[test]
+    fn parses_key_names_and_modifiers() {
+        assert_eq!(KeyBinding::parse("q").unwrap(), KeyBinding { code: KeyCode::Char('q'), modifiers: KeyModifiers::NONE });
+        assert_eq!(KeyBinding::parse("Space").unwrap().code, KeyCode::Char(' '));
+        let binding = KeyBinding::parse("Ctrl-Alt-Up").unwrap();
+        assert_eq!(binding, KeyBinding { code: KeyCode::Up, modifiers: KeyModifiers::CONTROL | KeyModifiers::ALT });
+        assert_eq!(binding.name(), "Ctrl-Alt-Up");
+        assert_eq!(KeyBinding::parse("Enterprise").unwrap_err(), "Unknown key `Enterprise`");
+        assert!(KeyBinding::parse("Ctrl-").is_err());
+
+        // Shift is part of the character, so "?" matches Shift-?
+        assert!(KeyBinding::parse("?").unwrap().matches(&KeyEvent::new(KeyCode::Char('?'), KeyModifiers::SHIFT)));
+        assert!(!KeyBinding::parse("s").unwrap().matches(&KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL)));
+    }
+
+    #[test]
+    fn parses_hex_and_named_colours() {
+        assert_eq!(parse_color("#ff8800"), Ok(Color::Rgb(255, 136, 0)));
+        assert_eq!(parse_color("LightMagenta"), Ok(Color::LightMagenta));
+        assert!(parse_color("#ff88").is_err());
+        assert!(parse_color("#gg8800").is_err());
+        assert!(parse_color("Pink").is_err());
+    }
+
+    #[test]
+    fn looks_up_presets() {
+        for name in ["default", "vim", "high-contrast"] {
+            assert!(TuiConfig::preset(name).is_some(), "{}", name);
+            assert!(KeyMap::preset(name).unwrap().conflicts().is_empty(), "{}", name);
+        }
+        assert!(TuiConfig::preset("emacs").is_none());
+        assert!(config("{}", Some("emacs")).is_err());
+    }
+
+    #[test]
+    fn merges_the_config_file_over_its_preset() {
+        let config = config(r##"{ "preset": "vim", "keys": { "save": ["Ctrl-s"] }, "theme": { "highlight": "#ff8800" } }"##, None).unwrap();
+        let ctrl_s = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(config.keymap.action_for(&ctrl_s), Some(Action::Save));
+        assert_eq!(config.keymap.action_for(&KeyEvent::new(KeyCode::Char('w'), KeyModifiers::NONE)), None);
+        assert_eq!(config.keymap.describe(Action::Down), "j/Down");
+        assert_eq!(config.theme.highlight, Color::Rgb(255, 136, 0));
+        assert_eq!(config.theme.status, Theme::preset("vim").unwrap().status);
+
+        // A preset on the command line replaces the file's, but its overrides still apply
+        let config = self::config(r##"{ "preset": "vim", "keys": { "save": ["Ctrl-s"] } }"##, Some("high-contrast")).unwrap();
+        assert_eq!(config.keymap.describe(Action::Down), "Down");
+        assert_eq!(config.keymap.describe(Action::Save), "Ctrl-s");
+    }
+
+    #[test]
+    fn rejects_conflicting_bindings_and_unknown_names() {
+        let error = config(r#"{ "keys": { "save": ["Enter"] } }"#, None).unwrap_err();
+        assert_eq!(error.to_string(), "Conflicting keybindings:\n  `Enter` is bound to both \"Go down a level or edit\" and \"Save the current family\"");
+        assert!(config(r#"{ "theme": { "sparkle": "Red" } }"#, None).is_err());
+        assert!(config(r#"{ "keys": { "save": ["Hyper-s"] } }"#, None).is_err());
+    }
+}
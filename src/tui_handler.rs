@@ -1,61 +1,186 @@
-use crossterm::event::{self, Event, KeyCode};
-use std::{io::{self, Write}, fs::OpenOptions};
+use crossterm::event::{self, Event, KeyCode, MouseButton, MouseEvent, MouseEventKind};
+use std::{io, time::{Duration, Instant}};
 use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
-    text::Spans,
+    style::{Modifier, Style},
+    text::{Span, Spans},
     widgets::{Block, Borders, Tabs, Paragraph, Clear},
     Frame, Terminal,
 };
 
 use crate::reg_gen::register::*;
 use crate::reg_gen::json_handling::*;
+use crate::reg_gen::format_handling::ConfigFormat;
+use crate::reg_gen::header_handling::create_register_class;
 use crate::vertical_tab::*;
+use crate::fuzzy_search::fuzzy_score;
+use crate::tui_config::*;
 
 // This file is responsible for all TUI operations. App stores the state of the TUI, and run_app
 // runs the TUI
 
-const BG_COLOR: Color = Color::Rgb(40, 44, 52);
+// Two clicks on the same cell within this time count as a double-click
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
 
 pub enum AppState {
+    SelectFamily,
     SelectRegisterAndField,
     SelectFieldInfo,
     EditFieldInfo,
+    SelectRegisterFromList,
+    Search,
+    SelectTransferTarget,
 }
 
-pub struct App {
-    pub original_path: String,
+// Whether a register being sent to another family stays in its original family as well
+pub enum Transfer {
+    Copy,
+    Move,
+}
+
+// One register family JSON open in the TUI, along with whether it has unsaved edits
+pub struct FamilyFile {
+    pub path: String,
     pub register_family: RegisterFamily,
+    pub dirty: bool,
+}
+
+impl FamilyFile {
+    pub fn new(path: String) -> Result<FamilyFile, std::io::Error> {
+        Ok(FamilyFile {
+            register_family: pull_existing_json(&path)?,
+            path,
+            dirty: false,
+        })
+    }
+
+    // Goes through the same validation as every other command, so a TUI edit can't save a
+    // configuration that won't generate
+    pub fn write_to_file(&mut self) -> Result<(), std::io::Error> {
+        write_register_family_as(&self.path, &self.register_family, ConfigFormat::of_path(&self.path))?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+// A register, or a field within one, that matched the search query
+pub struct SearchResult {
+    pub register_index: usize,
+    pub field_index: Option<usize>,
+    pub label: String,
+    pub score: i64,
+}
+
+pub struct App {
+    pub families: Vec<FamilyFile>,
+    pub family_index: usize,
     pub register_index: usize,
     pub field_index: usize,
     pub field_info_index: usize,
     pub state: AppState,
     pub input: String,
+    pub show_preview: bool,
+    pub field_tabs_state: VerticalTabsState,
+    pub field_info_tabs_state: VerticalTabsState,
+    pub register_filter: String,
+    pub register_list_index: usize,
+    pub register_list_state: VerticalTabsState,
+    pub search_query: String,
+    pub search_index: usize,
+    pub search_list_state: VerticalTabsState,
+    pub register_tab_areas: Vec<(Rect, usize)>,
+    pub last_click: Option<(Instant, u16, u16)>,
+    pub family_tab_areas: Vec<(Rect, usize)>,
+    pub transfer: Transfer,
+    pub transfer_target_index: usize,
+    pub status: String,
+    pub config: TuiConfig,
+    pub show_help: bool,
 }
 
 impl App {
-    pub fn new(path: String) -> App {
-        App {
-            original_path: path.clone(),
-            register_family: pull_existing_json(&path),
+    pub fn new(paths: Vec<String>, config: TuiConfig) -> Result<App, std::io::Error> {
+        Ok(App {
+            families: paths.into_iter().map(FamilyFile::new).collect::<Result<_, _>>()?,
+            family_index: 0,
             register_index: 0,
             field_index: 0,
             field_info_index: 0,
             state: AppState::SelectRegisterAndField,
             input: String::new(),
+            show_preview: false,
+            field_tabs_state: VerticalTabsState::default(),
+            field_info_tabs_state: VerticalTabsState::default(),
+            register_filter: String::new(),
+            register_list_index: 0,
+            register_list_state: VerticalTabsState::default(),
+            search_query: String::new(),
+            search_index: 0,
+            search_list_state: VerticalTabsState::default(),
+            register_tab_areas: Vec::new(),
+            last_click: None,
+            family_tab_areas: Vec::new(),
+            transfer: Transfer::Copy,
+            transfer_target_index: 0,
+            status: String::new(),
+            config,
+            show_help: false,
+        })
+    }
+
+    pub fn family(&self) -> &RegisterFamily {
+        &self.families[self.family_index].register_family
+    }
+
+    pub fn family_mut(&mut self) -> &mut RegisterFamily {
+        &mut self.families[self.family_index].register_family
+    }
+
+    pub fn current_field(&self) -> Option<&Field> {
+        self.family().registers.get(self.register_index)?.fields.get(self.field_index)
+    }
+
+    pub fn select_family(&mut self, index: usize) {
+        if index != self.family_index {
+            self.register_index = 0;
+            self.field_index = 0;
+            self.field_info_index = 0;
+        }
+        self.family_index = index;
+    }
+
+    pub fn next_family(&mut self) {
+        self.select_family((self.family_index + 1) % self.families.len());
+    }
+
+    pub fn previous_family(&mut self) {
+        if self.family_index > 0 {
+            self.select_family(self.family_index - 1);
+        } else {
+            self.select_family(self.families.len() - 1);
         }
     }
 
     pub fn next_register(&mut self) {
+        let length = self.family().registers.len();
+        if length == 0 {
+            return;
+        }
+
         // Reset field index
         self.field_index = 0;
         self.field_info_index = 0;
 
-        self.register_index = (self.register_index + 1) % self.register_family.registers.len();
+        self.register_index = (self.register_index + 1) % length;
     }
 
     pub fn previous_register(&mut self) {
+        let length = self.family().registers.len();
+        if length == 0 {
+            return;
+        }
+
         // Reset field index
         self.field_index = 0;
         self.field_info_index = 0;
@@ -63,26 +188,55 @@ impl App {
         if self.register_index > 0 {
             self.register_index -= 1;
         } else {
-            self.register_index = self.register_family.registers.len() - 1;
+            self.register_index = length - 1;
         }
     }
 
     pub fn next_field(&mut self) {
+        let length = self.field_count();
+        if length == 0 {
+            return;
+        }
+
         self.field_info_index = 0;
 
-        self.field_index = (self.field_index + 1) % self.register_family.registers[self.register_index].fields.len();
+        self.field_index = (self.field_index + 1) % length;
     }
 
     pub fn previous_field(&mut self) {
+        let length = self.field_count();
+        if length == 0 {
+            return;
+        }
+
         self.field_info_index = 0;
 
         if self.field_index > 0 {
             self.field_index -= 1;
         } else {
-            self.field_index = self.register_family.registers[self.register_index].fields.len() - 1;
+            self.field_index = length - 1;
         }
     }
 
+    fn field_count(&self) -> usize {
+        self.family().registers.get(self.register_index).map_or(0, |register| register.fields.len())
+    }
+
+    pub fn select_register(&mut self, index: usize) {
+        if index != self.register_index {
+            self.field_index = 0;
+            self.field_info_index = 0;
+        }
+        self.register_index = index;
+    }
+
+    pub fn select_field(&mut self, index: usize) {
+        if index != self.field_index {
+            self.field_info_index = 0;
+        }
+        self.field_index = index;
+    }
+
     pub fn next_field_info(&mut self) {
         // Mod 5 because there are 5 field info elements
         self.field_info_index = (self.field_info_index + 1) % 5;
@@ -97,8 +251,196 @@ impl App {
         }
     }
 
+    // Indices of the registers whose names contain the filter, ignoring case
+    pub fn filtered_registers(&self) -> Vec<usize> {
+        let filter = self.register_filter.to_lowercase();
+        self.family()
+            .registers
+            .iter()
+            .enumerate()
+            .filter(|(_, register)| register.name.to_lowercase().contains(&filter))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn open_register_list(&mut self) {
+        self.register_filter.clear();
+        self.register_list_index = self.register_index;
+        self.state = AppState::SelectRegisterFromList;
+    }
+
+    pub fn next_register_in_list(&mut self) {
+        let length = self.filtered_registers().len();
+        if length > 0 {
+            self.register_list_index = (self.register_list_index + 1) % length;
+        }
+    }
+
+    pub fn previous_register_in_list(&mut self) {
+        let length = self.filtered_registers().len();
+        if self.register_list_index > 0 {
+            self.register_list_index -= 1;
+        } else if length > 0 {
+            self.register_list_index = length - 1;
+        }
+    }
+
+    pub fn update_register_filter(&mut self) {
+        // The old position means nothing in the new list, start again from the top
+        self.register_list_index = 0;
+    }
+
+    pub fn select_register_from_list(&mut self) {
+        if let Some(&index) = self.filtered_registers().get(self.register_list_index) {
+            self.register_index = index;
+            self.field_index = 0;
+            self.field_info_index = 0;
+        }
+        self.state = AppState::SelectRegisterAndField;
+    }
+
+    // Fuzzy matches the query against every register name and every "register.field" name in the
+    // family, best match first
+    pub fn search_results(&self) -> Vec<SearchResult> {
+        let mut results = Vec::new();
+        for (register_index, register) in self.family().registers.iter().enumerate() {
+            if let Some(score) = fuzzy_score(&self.search_query, &register.name) {
+                results.push(SearchResult { register_index, field_index: None, label: register.name.clone(), score });
+            }
+            for (field_index, field) in register.fields.iter().enumerate() {
+                let label = format!("{}.{}", register.name, field.name);
+                // Only match against the register name too when the query spells out a path,
+                // otherwise every field of a matching register would match
+                let score = if self.search_query.contains('.') {
+                    fuzzy_score(&self.search_query, &label)
+                } else {
+                    fuzzy_score(&self.search_query, &field.name)
+                };
+                if let Some(score) = score {
+                    results.push(SearchResult { register_index, field_index: Some(field_index), label, score });
+                }
+            }
+        }
+        results.sort_by(|a, b| b.score.cmp(&a.score).then(a.label.len().cmp(&b.label.len())));
+        results
+    }
+
+    pub fn open_search(&mut self) {
+        self.search_query.clear();
+        self.search_index = 0;
+        self.state = AppState::Search;
+    }
+
+    pub fn next_search_result(&mut self) {
+        let length = self.search_results().len();
+        if length > 0 {
+            self.search_index = (self.search_index + 1) % length;
+        }
+    }
+
+    pub fn previous_search_result(&mut self) {
+        let length = self.search_results().len();
+        if self.search_index > 0 {
+            self.search_index -= 1;
+        } else if length > 0 {
+            self.search_index = length - 1;
+        }
+    }
+
+    pub fn jump_to_search_result(&mut self) {
+        if let Some(result) = self.search_results().get(self.search_index) {
+            self.register_index = result.register_index;
+            self.field_index = result.field_index.unwrap_or(0);
+            self.field_info_index = 0;
+        }
+        self.state = AppState::SelectRegisterAndField;
+    }
+
+    // Clicks select whatever is under the cursor, double-clicks act like Enter, and the scroll
+    // wheel moves through whichever list it is over
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let (column, row) = (mouse.column, mouse.row);
+        match self.state {
+            AppState::EditFieldInfo | AppState::SelectTransferTarget => (),
+            AppState::SelectRegisterFromList => match mouse.kind {
+                MouseEventKind::ScrollDown => self.next_register_in_list(),
+                MouseEventKind::ScrollUp => self.previous_register_in_list(),
+                _ => (),
+            },
+            AppState::Search => match mouse.kind {
+                MouseEventKind::ScrollDown => self.next_search_result(),
+                MouseEventKind::ScrollUp => self.previous_search_result(),
+                _ => (),
+            },
+            AppState::SelectFamily | AppState::SelectRegisterAndField | AppState::SelectFieldInfo => match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left) => {
+                    let double_click = self.is_double_click(column, row);
+                    self.click(column, row, double_click);
+                },
+                MouseEventKind::ScrollDown => self.scroll(column, row, true),
+                MouseEventKind::ScrollUp => self.scroll(column, row, false),
+                _ => (),
+            },
+        }
+    }
+
+    fn is_double_click(&mut self, column: u16, row: u16) -> bool {
+        let now = Instant::now();
+        let double_click = matches!(self.last_click,
+            Some((time, last_column, last_row)) if last_column == column && last_row == row && now.duration_since(time) <= DOUBLE_CLICK_TIME);
+        // A third click starts a new double-click rather than counting as another one
+        self.last_click = if double_click { None } else { Some((now, column, row)) };
+        double_click
+    }
+
+    fn click(&mut self, column: u16, row: u16, double_click: bool) {
+        let family_tab = self.family_tab_areas
+            .iter()
+            .find(|(area, _)| column >= area.left() && column < area.right() && row == area.top())
+            .map(|(_, index)| *index);
+        if let Some(index) = family_tab {
+            self.select_family(index);
+            self.state = AppState::SelectFamily;
+            return;
+        }
+
+        let tab = self.register_tab_areas
+            .iter()
+            .find(|(area, _)| column >= area.left() && column < area.right() && row == area.top())
+            .map(|(_, index)| *index);
+        if let Some(index) = tab {
+            self.select_register(index);
+            self.state = AppState::SelectRegisterAndField;
+        } else if let Some(index) = self.field_tabs_state.index_at(column, row) {
+            self.select_field(index);
+            self.state = if double_click { AppState::SelectFieldInfo } else { AppState::SelectRegisterAndField };
+        } else if let Some(index) = self.field_info_tabs_state.index_at(column, row) {
+            self.field_info_index = index;
+            self.state = if double_click { AppState::EditFieldInfo } else { AppState::SelectFieldInfo };
+        }
+    }
+
+    fn scroll(&mut self, column: u16, row: u16, down: bool) {
+        let over_family_tabs = self.family_tab_areas.first().is_some_and(|(area, _)| row == area.top());
+        let over_tabs = self.register_tab_areas.first().is_some_and(|(area, _)| row == area.top());
+        if over_family_tabs {
+            if down { self.next_family() } else { self.previous_family() }
+        } else if over_tabs {
+            if down { self.next_register() } else { self.previous_register() }
+        } else if self.field_tabs_state.area.intersects(Rect::new(column, row, 1, 1)) {
+            if down { self.next_field() } else { self.previous_field() }
+        } else if self.field_info_tabs_state.area.intersects(Rect::new(column, row, 1, 1)) {
+            if down { self.next_field_info() } else { self.previous_field_info() }
+        }
+    }
+
     pub fn set_field_info(&mut self) {
-        let field = &mut self.register_family.registers[self.register_index].fields[self.field_index];
+        let (register_index, field_index) = (self.register_index, self.field_index);
+        let family = &mut self.families[self.family_index];
+        let Some(field) = family.register_family.registers.get_mut(register_index).and_then(|register| register.fields.get_mut(field_index)) else {
+            return;
+        };
+        let before = (field.lsb, field.msb, field.read, field.write, field.negative);
         match self.field_info_index {
             0 => {
                 if let Ok(x) = self.input.trim().parse() {
@@ -127,67 +469,258 @@ impl App {
             },
             _ => (),
         }
+        if before != (field.lsb, field.msb, field.read, field.write, field.negative) {
+            family.dirty = true;
+        }
     }
-    pub fn write_to_file(&mut self) -> Result<(), std::io::Error> {
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(false)
-            .truncate(true)
-            .open(self.original_path.clone())
-            .expect("Could not open JSON file!");
-
-        return match file.write_all(serde_json::to_string_pretty(&self.register_family).unwrap().as_bytes()) {
-            Ok(_) => Ok(()),
-            Err(why) => Err(std::io::Error::new(why.kind(), format!("Couldn't write to {}: {}", self.original_path, why))),
+
+    // Failures are reported in the status line, so the edits stay open to be fixed and saved again
+    pub fn save_family(&mut self) {
+        let family = &mut self.families[self.family_index];
+        self.status = match family.write_to_file() {
+            Ok(()) => format!("Saved {}", family.path),
+            Err(why) => status_message(&why),
         };
     }
+
+    // Writes every family with unsaved edits, used when quitting. Returns false if any couldn't be
+    // written, after trying the rest, so the TUI stays open rather than losing them. Edits that
+    // can't be saved, like a hand-edited file that fails validation, can still be discarded.
+    pub fn save_all(&mut self) -> bool {
+        let failures: Vec<String> = self.families
+            .iter_mut()
+            .filter(|family| family.dirty)
+            .filter_map(|family| family.write_to_file().err())
+            .map(|why| status_message(&why))
+            .collect();
+        if failures.is_empty() {
+            return true;
+        }
+        // The hint goes first, as long failures are cut off at the edge of the screen
+        let discard_keys = self.config.keymap.describe(Action::QuitWithoutSaving);
+        self.status = failures.join(" ");
+        if !discard_keys.is_empty() {
+            self.status = format!("Press {} to quit without saving. {}", discard_keys, self.status);
+        }
+        false
+    }
+
+    pub fn open_transfer(&mut self, transfer: Transfer) {
+        if self.families.len() < 2 {
+            self.status = String::from("Open more than one family to copy or move registers");
+            return;
+        }
+        if self.family().registers.get(self.register_index).is_none() {
+            self.status = String::from("There is no register to copy or move");
+            return;
+        }
+        self.transfer = transfer;
+        self.transfer_target_index = if self.family_index == 0 { 1 } else { 0 };
+        self.state = AppState::SelectTransferTarget;
+    }
+
+    // Moves through the other families, skipping the one the register is coming from
+    pub fn next_transfer_target(&mut self) {
+        let length = self.families.len();
+        self.transfer_target_index = (self.transfer_target_index + 1) % length;
+        if self.transfer_target_index == self.family_index {
+            self.transfer_target_index = (self.transfer_target_index + 1) % length;
+        }
+    }
+
+    pub fn previous_transfer_target(&mut self) {
+        let length = self.families.len();
+        self.transfer_target_index = (self.transfer_target_index + length - 1) % length;
+        if self.transfer_target_index == self.family_index {
+            self.transfer_target_index = (self.transfer_target_index + length - 1) % length;
+        }
+    }
+
+    pub fn transfer_register(&mut self) {
+        self.state = AppState::SelectRegisterAndField;
+        let register = self.family().registers[self.register_index].clone();
+        let target = &mut self.families[self.transfer_target_index];
+
+        // Two classes with the same name can't live in one generated header
+        if target.register_family.registers.iter().any(|existing| existing.name == register.name) {
+            self.status = format!("{} already has a register named {}", target.register_family.register_family, register.name);
+            return;
+        }
+
+        if !target.register_family.register_family_widths.contains(&register.size) {
+            target.register_family.register_family_widths.push(register.size);
+        }
+        let target_name = target.register_family.register_family.clone();
+        target.register_family.registers.push(register);
+        target.dirty = true;
+
+        let name = &self.family().registers[self.register_index].name;
+        self.status = match self.transfer {
+            Transfer::Copy => format!("Copied {} to {}", name, target_name),
+            Transfer::Move => format!("Moved {} to {}", name, target_name),
+        };
+
+        if matches!(self.transfer, Transfer::Move) {
+            let register_index = self.register_index;
+            self.family_mut().registers.remove(register_index);
+            self.families[self.family_index].dirty = true;
+            self.register_index = register_index.min(self.family().registers.len().saturating_sub(1));
+            self.field_index = 0;
+            self.field_info_index = 0;
+        }
+    }
+}
+
+// Errors on one line for the status line
+fn status_message(why: &io::Error) -> String {
+    why.to_string().lines().map(str::trim).collect::<Vec<&str>>().join(" ")
+}
+
+// Where run_app gets its input from, so tests can script key presses instead of reading the
+// terminal
+pub trait EventSource {
+    fn next_event(&mut self) -> io::Result<Event>;
+}
+
+pub struct TerminalEvents;
+
+impl EventSource for TerminalEvents {
+    fn next_event(&mut self) -> io::Result<Event> {
+        event::read()
+    }
 }
 
-pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
+pub fn run_app<B: Backend, E: EventSource>(terminal: &mut Terminal<B>, mut app: App, events: &mut E) -> io::Result<()> {
     loop {
         terminal.draw(|f| ui(f, &mut app))?;
 
-        if let Event::Key(key) = event::read()? {
-            match app.state {
-                AppState::SelectRegisterAndField => {
-                    match key.code {
-                        KeyCode::Char('q') => return app.write_to_file(),
-                        KeyCode::Right => app.next_register(),
-                        KeyCode::Left => app.previous_register(),
-                        KeyCode::Up => app.previous_field(),
-                        KeyCode::Down => app.next_field(),
-                        KeyCode::Enter => app.state = AppState::SelectFieldInfo,
-                        _ => ()
-                    }
-                },
-                AppState::SelectFieldInfo => {
-                    match key.code {
-                        KeyCode::Char('q') => return app.write_to_file(),
-                        KeyCode::Up => app.previous_field_info(),
-                        KeyCode::Down => app.next_field_info(),
-                        KeyCode::Enter => app.state = AppState::EditFieldInfo,
-                        KeyCode::Esc => app.state = AppState::SelectRegisterAndField,
-                        _ => ()
-                    }
-                },
-                AppState::EditFieldInfo => {
-                    match key.code {
-                        KeyCode::Char(ch) => {
-                            app.input.push(ch);
-                        },
-                        KeyCode::Backspace => {
-                            app.input.pop();
-                        },
-                        KeyCode::Enter => {
-                            app.set_field_info();
-                        },
-                        KeyCode::Esc => {
-                            app.input.clear();
-                            app.state = AppState::SelectFieldInfo;
-                        },
-                        _ => ()
-                    }
+        let key = match events.next_event()? {
+            Event::Key(key) => key,
+            Event::Mouse(mouse) => {
+                app.handle_mouse(mouse);
+                continue;
+            },
+            _ => continue,
+        };
+        // Messages only last until the next key press
+        app.status.clear();
+
+        // Any key closes the help overlay
+        if app.show_help {
+            app.show_help = false;
+            continue;
+        }
+
+        // Text entry states read keys directly, everything else goes through the keymap
+        let action = app.config.keymap.action_for(&key);
+        match app.state {
+            AppState::SelectFamily => {
+                match action {
+                    Some(Action::Quit) if app.save_all() => return Ok(()),
+                    Some(Action::QuitWithoutSaving) => return Ok(()),
+                    Some(Action::Save) => app.save_family(),
+                    Some(Action::Right) => app.next_family(),
+                    Some(Action::Left) => app.previous_family(),
+                    Some(Action::Select) | Some(Action::Down) => app.state = AppState::SelectRegisterAndField,
+                    Some(Action::TogglePreview) => app.show_preview = !app.show_preview,
+                    Some(Action::Help) => app.show_help = true,
+                    _ => ()
+                }
+            },
+            AppState::SelectRegisterAndField => {
+                match action {
+                    Some(Action::Quit) if app.save_all() => return Ok(()),
+                    Some(Action::QuitWithoutSaving) => return Ok(()),
+                    Some(Action::Save) => app.save_family(),
+                    Some(Action::Right) => app.next_register(),
+                    Some(Action::Left) => app.previous_register(),
+                    Some(Action::Up) => app.previous_field(),
+                    Some(Action::Down) => app.next_field(),
+                    Some(Action::Select) if app.current_field().is_some() => app.state = AppState::SelectFieldInfo,
+                    Some(Action::Back) => app.state = AppState::SelectFamily,
+                    Some(Action::TogglePreview) => app.show_preview = !app.show_preview,
+                    Some(Action::RegisterList) => app.open_register_list(),
+                    Some(Action::Search) => app.open_search(),
+                    Some(Action::CopyRegister) => app.open_transfer(Transfer::Copy),
+                    Some(Action::MoveRegister) => app.open_transfer(Transfer::Move),
+                    Some(Action::Help) => app.show_help = true,
+                    _ => ()
+                }
+            },
+            AppState::SelectFieldInfo => {
+                match action {
+                    Some(Action::Quit) if app.save_all() => return Ok(()),
+                    Some(Action::QuitWithoutSaving) => return Ok(()),
+                    Some(Action::Save) => app.save_family(),
+                    Some(Action::Up) => app.previous_field_info(),
+                    Some(Action::Down) => app.next_field_info(),
+                    Some(Action::Select) => app.state = AppState::EditFieldInfo,
+                    Some(Action::Back) => app.state = AppState::SelectRegisterAndField,
+                    Some(Action::TogglePreview) => app.show_preview = !app.show_preview,
+                    Some(Action::Search) => app.open_search(),
+                    Some(Action::Help) => app.show_help = true,
+                    _ => ()
+                }
+            },
+            AppState::EditFieldInfo => {
+                match key.code {
+                    KeyCode::Char(ch) => {
+                        app.input.push(ch);
+                    },
+                    KeyCode::Backspace => {
+                        app.input.pop();
+                    },
+                    KeyCode::Enter => {
+                        app.set_field_info();
+                    },
+                    KeyCode::Esc => {
+                        app.input.clear();
+                        app.state = AppState::SelectFieldInfo;
+                    },
+                    _ => ()
+                }
+            },
+            AppState::SelectRegisterFromList => {
+                match key.code {
+                    KeyCode::Char(ch) => {
+                        app.register_filter.push(ch);
+                        app.update_register_filter();
+                    },
+                    KeyCode::Backspace => {
+                        app.register_filter.pop();
+                        app.update_register_filter();
+                    },
+                    KeyCode::Up => app.previous_register_in_list(),
+                    KeyCode::Down => app.next_register_in_list(),
+                    KeyCode::Enter => app.select_register_from_list(),
+                    KeyCode::Esc => app.state = AppState::SelectRegisterAndField,
+                    _ => ()
+                }
+            },
+            AppState::Search => {
+                match key.code {
+                    KeyCode::Char(ch) => {
+                        app.search_query.push(ch);
+                        app.search_index = 0;
+                    },
+                    KeyCode::Backspace => {
+                        app.search_query.pop();
+                        app.search_index = 0;
+                    },
+                    KeyCode::Up => app.previous_search_result(),
+                    KeyCode::Down => app.next_search_result(),
+                    KeyCode::Enter => app.jump_to_search_result(),
+                    KeyCode::Esc => app.state = AppState::SelectRegisterAndField,
+                    _ => ()
+                }
+            },
+            AppState::SelectTransferTarget => {
+                match action {
+                    Some(Action::Up) => app.previous_transfer_target(),
+                    Some(Action::Down) => app.next_transfer_target(),
+                    Some(Action::Select) => app.transfer_register(),
+                    Some(Action::Back) => app.state = AppState::SelectRegisterAndField,
+                    _ => ()
                 }
             }
         }
@@ -201,49 +734,180 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(3)
-        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .constraints([Constraint::Length(3), Constraint::Length(3), Constraint::Min(0)].as_ref())
         .split(size);
 
     // Full screen block I think
-    let block = Block::default().style(Style::default().bg(BG_COLOR).fg(Color::White));
+    let theme = app.config.theme.clone();
+    let block = Block::default().style(Style::default().bg(theme.background).fg(theme.foreground));
     f.render_widget(block, size);
 
-    draw_register_tabs(f, app, chunks[0]);
-    draw_register_view(f, app, chunks[1]);
+    draw_family_tabs(f, app, chunks[0]);
+    draw_register_tabs(f, app, chunks[1]);
+    if app.show_preview {
+        // Split the main area so the generated code sits beside the register it came from
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(chunks[2]);
+        draw_register_view(f, app, panes[0]);
+        draw_preview(f, app, panes[1]);
+    } else {
+        draw_register_view(f, app, chunks[2]);
+    }
+
+    // Messages go in the bottom margin
+    if !app.status.is_empty() && size.height > 2 {
+        let area = Rect::new(chunks[2].x, size.bottom() - 2, chunks[2].width, 1);
+        let paragraph = Paragraph::new(Spans::from(app.status.clone()))
+            .style(Style::default().bg(theme.background).fg(theme.status));
+        f.render_widget(paragraph, area);
+    }
     
     if matches!(app.state, AppState::EditFieldInfo) {
         draw_popup(f, app, size);
     }
+
+    if matches!(app.state, AppState::SelectRegisterFromList) {
+        draw_register_list(f, app, size);
+    }
+
+    if matches!(app.state, AppState::Search) {
+        draw_search(f, app, size);
+    }
+
+    if matches!(app.state, AppState::SelectTransferTarget) {
+        draw_transfer_targets(f, app, size);
+    }
+
+    if app.show_help {
+        draw_help(f, app, size);
+    }
 }
 
-fn draw_register_tabs<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
-    // Create the tabs
-    let titles = app
-        .register_family
-        .registers
+// Selection style for a list, depending on whether the list currently has focus
+fn highlight_style(theme: &Theme, focused: bool) -> Style {
+    let style = Style::default().add_modifier(Modifier::BOLD);
+    if focused {
+        style.bg(theme.highlight).fg(theme.highlight_foreground)
+    } else {
+        style.bg(theme.inactive_highlight)
+    }
+}
+
+fn draw_family_tabs<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
+    // Families with unsaved edits are marked with a star
+    let titles: Vec<Spans> = app
+        .families
         .iter()
-        .map(|register| {
-            Spans::from(register.name.clone())
+        .map(|family| {
+            let marker = if family.dirty { "*" } else { "" };
+            Spans::from(format!("{}{}", family.register_family.register_family, marker))
         })
         .collect();
+
+    app.family_tab_areas.clear();
+    let mut x = area.left() + 1;
+    for (i, title) in titles.iter().enumerate() {
+        x += 1;
+        let width = title.width() as u16;
+        app.family_tab_areas.push((Rect::new(x, area.top() + 1, width, 1), i));
+        x += width + 2;
+    }
+
     let tabs = Tabs::new(titles)
-        .block(Block::default().borders(Borders::ALL).title("Tabs"))
-        .select(app.register_index)
-        .style(Style::default().fg(Color::White))
-        .highlight_style(
-            Style::default()
-                .add_modifier(Modifier::BOLD)
-                .bg(if matches!(app.state, AppState::SelectRegisterAndField) { Color::LightMagenta } else { Color::DarkGray }),
-        );
+        .block(Block::default().borders(Borders::ALL).title("Families"))
+        .select(app.family_index)
+        .style(Style::default().fg(app.config.theme.foreground))
+        .highlight_style(highlight_style(&app.config.theme, matches!(app.state, AppState::SelectFamily)));
     f.render_widget(tabs, area);
 }
 
+fn draw_register_tabs<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
+    let registers = &app.families[app.family_index].register_family.registers;
+
+    // Only the tabs that fit around the selected register are drawn, the rest are counted
+    let widths: Vec<usize> = registers.iter().map(|register| register.name.chars().count()).collect();
+    let (start, end) = visible_tab_window(&widths, app.register_index, area.width.saturating_sub(2) as usize);
+
+    let mut titles = Vec::new();
+    if start > 0 {
+        titles.push(Spans::from(Span::styled(format!("◀ {}", start), Style::default().fg(app.config.theme.muted))));
+    }
+    for register in &registers[start..end] {
+        titles.push(Spans::from(register.name.clone()));
+    }
+    if end < registers.len() {
+        titles.push(Spans::from(Span::styled(format!("{} ▶", registers.len() - end), Style::default().fg(app.config.theme.muted))));
+    }
+    let selected = app.register_index - start + if start > 0 { 1 } else { 0 };
+
+    // Remember where each tab lands so clicks can be mapped back to registers. The indicators
+    // select the first hidden register on their side.
+    let mut tab_registers: Vec<usize> = Vec::new();
+    if start > 0 {
+        tab_registers.push(start - 1);
+    }
+    tab_registers.extend(start..end);
+    if end < registers.len() {
+        tab_registers.push(end);
+    }
+    app.register_tab_areas.clear();
+    let mut x = area.left() + 1;
+    for (title, register) in titles.iter().zip(tab_registers) {
+        x += 1;
+        let width = title.width() as u16;
+        app.register_tab_areas.push((Rect::new(x, area.top() + 1, width, 1), register));
+        x += width + 2;
+    }
+
+    let tabs = Tabs::new(titles)
+        .block(Block::default().borders(Borders::ALL).title(format!("Tabs ({}/{}, {} to list)", (app.register_index + 1).min(registers.len()), registers.len(), app.config.keymap.describe(Action::RegisterList))))
+        .select(selected)
+        .style(Style::default().fg(app.config.theme.foreground))
+        .highlight_style(highlight_style(&app.config.theme, matches!(app.state, AppState::SelectRegisterAndField)));
+    f.render_widget(tabs, area);
+}
+
+// Picks the range of tabs to draw so that the selected tab is always visible. Each tab takes its
+// title plus a space either side, and a divider between tabs.
+fn visible_tab_window(widths: &[usize], selected: usize, available: usize) -> (usize, usize) {
+    let total: usize = widths.iter().map(|width| width + 3).sum();
+    if widths.is_empty() || total <= available + 1 {
+        return (0, widths.len());
+    }
+
+    // Leave room for the "◀ n" and "n ▶" indicators
+    let indicator = format!("{}", widths.len()).len() + 2 + 3;
+    let available = available.saturating_sub(2 * indicator);
+
+    let (mut start, mut end) = (selected, selected + 1);
+    let mut used = widths[selected] + 2;
+    loop {
+        let mut grew = false;
+        if end < widths.len() && used + widths[end] + 3 <= available {
+            used += widths[end] + 3;
+            end += 1;
+            grew = true;
+        }
+        if start > 0 && used + widths[start - 1] + 3 <= available {
+            used += widths[start - 1] + 3;
+            start -= 1;
+            grew = true;
+        }
+        if !grew {
+            break;
+        }
+    }
+    (start, end)
+}
+
 fn draw_register_view<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
     // Draw a box around the entire register view
     let block = Block::default()
         .style(Style::default()
-            .bg(BG_COLOR)
-            .fg(Color::White))
+            .bg(app.config.theme.background)
+            .fg(app.config.theme.foreground))
         .borders(Borders::ALL)
         .title("Register View");
     f.render_widget(block, area);
@@ -260,9 +924,12 @@ fn draw_register_view<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: B
 }
 
 fn draw_register_information<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
-    let text = vec![Spans::from(format!("Register Name: {}", app.register_family.registers[app.register_index].name)), Spans::from(format!("Size: {}-Bit", app.register_family.registers[app.register_index].size))];
+    let text = match app.family().registers.get(app.register_index) {
+        Some(register) => vec![Spans::from(format!("Register Name: {}", register.name)), Spans::from(format!("Size: {}-Bit", register.size))],
+        None => vec![Spans::from("This family has no registers")],
+    };
     let paragraph = Paragraph::new(text.clone())
-        .style(Style::default().bg(BG_COLOR).fg(Color::White).add_modifier(Modifier::BOLD));
+        .style(Style::default().bg(app.config.theme.background).fg(app.config.theme.foreground).add_modifier(Modifier::BOLD));
 
     f.render_widget(paragraph, area);
 }
@@ -270,8 +937,8 @@ fn draw_register_information<B>(f: &mut Frame<B>, app: &mut App, area: Rect) whe
 fn draw_field_view<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
     let block = Block::default()
         .style(Style::default()
-            .bg(BG_COLOR)
-            .fg(Color::White))
+            .bg(app.config.theme.background)
+            .fg(app.config.theme.foreground))
         .borders(Borders::ALL)
         .title("Field View");
 
@@ -290,44 +957,84 @@ fn draw_field_view<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Back
 fn draw_field_tabs<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
     // Create the tabs
     let titles = app
+        .families[app.family_index]
         .register_family
-        .registers[app.register_index]
-        .fields
-        .iter()
-        .map(|field| {
-            Spans::from(field.name.clone())
-        })
-        .collect();
+        .registers
+        .get(app.register_index)
+        .map_or(Vec::new(), |register| register.fields.iter().map(|field| Spans::from(field.name.clone())).collect());
     let tabs = VerticalTabs::new(titles)
         .block(Block::default().borders(Borders::ALL).title("Fields"))
         .select(app.field_index)
-        .style(Style::default().fg(Color::White))
-        .highlight_style(
-            Style::default()
-                .add_modifier(Modifier::BOLD)
-                .bg(if matches!(app.state, AppState::SelectRegisterAndField) { Color::LightMagenta } else { Color::DarkGray }),
-        );
-    f.render_widget(tabs, area);
+        .style(Style::default().fg(app.config.theme.foreground))
+        .highlight_style(highlight_style(&app.config.theme, matches!(app.state, AppState::SelectRegisterAndField)));
+    f.render_stateful_widget(tabs, area, &mut app.field_tabs_state);
 }
 
 fn draw_field_info_tabs<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
-    let field = &app.register_family.registers[app.register_index].fields[app.field_index];
-    let titles = vec![
-        Spans::from(format!("LSB: {}", field.lsb)),
-        Spans::from(format!("MSB: {}", field.msb)),
-        Spans::from(format!("Read: {}", field.read)),
-        Spans::from(format!("Write: {}", field.write)),
-        Spans::from(format!("Negative: {}", if let Some(n) = field.negative {n} else {false}))];
+    let titles = match app.current_field() {
+        Some(field) => vec![
+            Spans::from(format!("LSB: {}", field.lsb)),
+            Spans::from(format!("MSB: {}", field.msb)),
+            Spans::from(format!("Read: {}", field.read)),
+            Spans::from(format!("Write: {}", field.write)),
+            Spans::from(format!("Negative: {}", field.negative.unwrap_or_default()))],
+        None => Vec::new(),
+    };
     let tabs = VerticalTabs::new(titles)
         .block(Block::default().borders(Borders::ALL).title("Fields"))
         .select(app.field_info_index)
-        .style(Style::default().fg(Color::White))
-        .highlight_style(
-            Style::default()
-                .add_modifier(Modifier::BOLD)
-                .bg(if matches!(app.state, AppState::SelectFieldInfo) { Color::LightMagenta } else { Color::DarkGray }),
-        );
-    f.render_widget(tabs, area);
+        .style(Style::default().fg(app.config.theme.foreground))
+        .highlight_style(highlight_style(&app.config.theme, matches!(app.state, AppState::SelectFieldInfo)));
+    f.render_stateful_widget(tabs, area, &mut app.field_info_tabs_state);
+}
+
+fn draw_preview<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
+    let block = Block::default().borders(Borders::ALL).title("Generated Code");
+    let Some(register) = app.family().registers.get(app.register_index) else {
+        f.render_widget(block.style(Style::default().bg(app.config.theme.background).fg(app.config.theme.foreground)), area);
+        return;
+    };
+    let selected_name = register.fields.get(app.field_index).map(|field| field.name.as_str());
+    let class = create_register_class(register, false);
+
+    // Highlight every line of the get/set methods that belong to the selected field
+    let mut lines = Vec::new();
+    let mut highlighted = Vec::new();
+    let mut in_selected_method = false;
+    for (i, raw_line) in class.lines().enumerate() {
+        if let Some(name) = selected_name {
+            let is_method = raw_line.starts_with("\tinline");
+            if is_method && (raw_line.contains(&format!(" get_{}(", name)) || raw_line.contains(&format!(" set_{}(", name))) {
+                in_selected_method = true;
+            }
+        }
+
+        // Tabs are not expanded by the terminal buffer, so do it here
+        let line = raw_line.replace('\t', "    ");
+        if in_selected_method {
+            highlighted.push(i);
+            lines.push(Spans::from(Span::styled(line, Style::default().fg(app.config.theme.highlight).add_modifier(Modifier::BOLD))));
+        } else {
+            lines.push(Spans::from(line));
+        }
+
+        if in_selected_method && raw_line == "\t}" {
+            in_selected_method = false;
+        }
+    }
+
+    // Only scroll once the selected field would fall off the bottom of the pane
+    let inner_height = area.height.saturating_sub(2) as usize;
+    let scroll = match (highlighted.first(), highlighted.last()) {
+        (Some(first), Some(last)) if *last >= inner_height => first.saturating_sub(2),
+        _ => 0,
+    };
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .style(Style::default().bg(app.config.theme.background).fg(app.config.theme.foreground))
+        .scroll((scroll as u16, 0));
+    f.render_widget(paragraph, area);
 }
 
 fn draw_popup<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
@@ -349,6 +1056,115 @@ fn draw_popup<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
     draw_input_prompt(f, app, chunks[1]);
 }
 
+fn draw_register_list<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
+    let area = centered_rect(60, 60, area);
+    f.render_widget(Clear, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    // The filter being typed
+    let paragraph = Paragraph::new(Spans::from(vec![Span::raw(app.register_filter.clone()), Span::styled(" ", Style::default().bg(app.config.theme.foreground))]))
+        .block(Block::default().borders(Borders::ALL).title("Filter Registers"))
+        .style(Style::default().bg(app.config.theme.background).fg(app.config.theme.foreground));
+    f.render_widget(paragraph, chunks[0]);
+
+    // Every register matching it
+    let filtered = app.filtered_registers();
+    let titles = filtered
+        .iter()
+        .map(|&i| {
+            let register = &app.family().registers[i];
+            Spans::from(format!("{} ({}-Bit)", register.name, register.size))
+        })
+        .collect();
+    let tabs = VerticalTabs::new(titles)
+        .block(Block::default().borders(Borders::ALL).title(format!("Registers ({} matching)", filtered.len())))
+        .select(app.register_list_index)
+        .style(Style::default().bg(app.config.theme.background).fg(app.config.theme.foreground))
+        .highlight_style(highlight_style(&app.config.theme, true));
+    f.render_stateful_widget(tabs, chunks[1], &mut app.register_list_state);
+}
+
+fn draw_search<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
+    let area = centered_rect(60, 60, area);
+    f.render_widget(Clear, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    // The query being typed
+    let paragraph = Paragraph::new(Spans::from(vec![Span::raw(format!("/{}", app.search_query)), Span::styled(" ", Style::default().bg(app.config.theme.foreground))]))
+        .block(Block::default().borders(Borders::ALL).title("Search Registers and Fields"))
+        .style(Style::default().bg(app.config.theme.background).fg(app.config.theme.foreground));
+    f.render_widget(paragraph, chunks[0]);
+
+    // Ranked results, fields are marked so they can be told apart from registers
+    let results = app.search_results();
+    let titles = results
+        .iter()
+        .map(|result| {
+            let kind = if result.field_index.is_some() { "field" } else { "register" };
+            Spans::from(vec![Span::raw(result.label.clone()), Span::styled(format!("  {}", kind), Style::default().fg(app.config.theme.muted))])
+        })
+        .collect();
+    let tabs = VerticalTabs::new(titles)
+        .block(Block::default().borders(Borders::ALL).title(format!("Results ({})", results.len())))
+        .select(app.search_index)
+        .style(Style::default().bg(app.config.theme.background).fg(app.config.theme.foreground))
+        .highlight_style(highlight_style(&app.config.theme, true));
+    f.render_stateful_widget(tabs, chunks[1], &mut app.search_list_state);
+}
+
+fn draw_transfer_targets<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
+    let area = centered_rect(40, 40, area);
+    f.render_widget(Clear, area);
+
+    let action = match app.transfer {
+        Transfer::Copy => "Copy",
+        Transfer::Move => "Move",
+    };
+    let titles = app
+        .families
+        .iter()
+        .map(|family| Spans::from(family.register_family.register_family.clone()))
+        .collect();
+    let tabs = VerticalTabs::new(titles)
+        .block(Block::default().borders(Borders::ALL).title(format!("{} {} to", action, app.family().registers[app.register_index].name)))
+        .select(app.transfer_target_index)
+        .style(Style::default().bg(app.config.theme.background).fg(app.config.theme.foreground))
+        .highlight_style(highlight_style(&app.config.theme, true));
+    f.render_widget(tabs, area);
+}
+
+fn draw_help<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
+    let area = centered_rect(70, 80, area);
+    f.render_widget(Clear, area);
+
+    let keymap = &app.config.keymap;
+    let width = Action::ALL.iter().map(|action| keymap.describe(*action).chars().count()).max().unwrap_or(0);
+    let mut lines: Vec<Spans> = Action::ALL
+        .iter()
+        .map(|action| {
+            Spans::from(vec![
+                Span::styled(format!("{:>width$}  ", keymap.describe(*action), width = width), Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(action.description()),
+            ])
+        })
+        .collect();
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled("Text entry: Enter confirms, Esc cancels, Up/Down picks a result", Style::default().fg(app.config.theme.muted))));
+
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Help (any key to close)"))
+        .style(Style::default().bg(app.config.theme.background).fg(app.config.theme.foreground));
+    f.render_widget(paragraph, area);
+}
+
 fn draw_input_prompt<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Backend {
     // Splitting into 4 chunks, chunk 0 is the prompt, chunk 1 is the input, chunk 2 is the cursor,
     // and chunk 3 pushes cursor to end of prompt
@@ -361,22 +1177,24 @@ fn draw_input_prompt<B>(f: &mut Frame<B>, app: &mut App, area: Rect) where B: Ba
     let (key, _value) = get_selected_field_as_string(app);
     let text = vec![Spans::from(format!("Set {} to: ", key))];
     let paragraph = Paragraph::new(text)
-        .alignment(tui::layout::Alignment::Right).style(Style::default().bg(BG_COLOR).fg(Color::White));
+        .alignment(tui::layout::Alignment::Right).style(Style::default().bg(app.config.theme.background).fg(app.config.theme.foreground));
     f.render_widget(paragraph, chunks[0]);
 
     // Draw the current input
     let text = vec![Spans::from(app.input.clone())];
     let paragraph = Paragraph::new(text)
-        .alignment(tui::layout::Alignment::Left).style(Style::default().bg(Color::Magenta).fg(Color::White));
+        .alignment(tui::layout::Alignment::Left).style(Style::default().bg(app.config.theme.input).fg(app.config.theme.foreground));
     f.render_widget(paragraph, chunks[1]);
 
     // Draw the cursor
-    let cursor_block = Block::default().style(Style::default().bg(Color::White));
+    let cursor_block = Block::default().style(Style::default().bg(app.config.theme.foreground));
     f.render_widget(cursor_block, chunks[2]);
 }
 
 fn get_selected_field_as_string(app: &App) -> (String, String) {
-    let field = &app.register_family.registers[app.register_index].fields[app.field_index];
+    let Some(field) = app.current_field() else {
+        return (String::from("ERROR"), String::from("ERROR"));
+    };
     match app.field_info_index {
         0 => (String::from("LSB"), field.lsb.to_string()),
         1 => (String::from("MSB"), field.msb.to_string()),
@@ -412,3 +1230,6 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         )
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests;
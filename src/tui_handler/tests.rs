@@ -0,0 +1,272 @@
+use std::{collections::VecDeque, fs, path::PathBuf};
+
+use crossterm::event::{KeyEvent, KeyModifiers, MouseEvent};
+use tui::backend::TestBackend;
+
+use super::*;
+
+// Plays back a fixed list of events, then reports end of input so run_app returns
+struct ScriptedEvents(VecDeque<Event>);
+
+impl EventSource for ScriptedEvents {
+    fn next_event(&mut self) -> io::Result<Event> {
+        self.0.pop_front().ok_or(io::Error::new(io::ErrorKind::UnexpectedEof, "End of script"))
+    }
+}
+
+fn keys(codes: &[KeyCode]) -> ScriptedEvents {
+    ScriptedEvents(codes.iter().map(|code| Event::Key(KeyEvent::new(*code, KeyModifiers::NONE))).collect())
+}
+
+fn mouse(kind: MouseEventKind, column: u16, row: u16) -> Event {
+    Event::Mouse(MouseEvent { kind, column, row, modifiers: KeyModifiers::NONE })
+}
+
+fn click(column: u16, row: u16) -> Event {
+    mouse(MouseEventKind::Down(MouseButton::Left), column, row)
+}
+
+fn text(text: &str) -> Vec<KeyCode> {
+    text.chars().map(KeyCode::Char).collect()
+}
+
+// Copies configurations from test/cfg so saving doesn't modify them
+fn copy_configs(test_name: &str, file_names: &[&str]) -> Vec<String> {
+    let dir = std::env::temp_dir().join("register_generator_tui_tests").join(test_name);
+    // Start from scratch, as a test may have left something other than a file behind
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    file_names
+        .iter()
+        .map(|file_name| {
+            let destination = dir.join(file_name);
+            fs::copy(PathBuf::from("test/cfg").join(file_name), &destination).unwrap();
+            destination.to_string_lossy().to_string()
+        })
+        .collect()
+}
+
+// Runs the script against a fresh app and returns the last frame drawn as text
+fn run(paths: Vec<String>, mut events: ScriptedEvents) -> (io::Result<()>, String) {
+    let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+    let app = App::new(paths, TuiConfig::default()).unwrap();
+    let result = run_app(&mut terminal, app, &mut events);
+    (result, buffer_to_string(terminal.backend().buffer()))
+}
+
+fn buffer_to_string(buffer: &tui::buffer::Buffer) -> String {
+    let area = buffer.area;
+    (area.top()..area.bottom())
+        .map(|y| {
+            let line: String = (area.left()..area.right()).map(|x| buffer.get(x, y).symbol.clone()).collect();
+            line.trim_end().to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn is_end_of_script(result: &io::Result<()>) -> bool {
+    matches!(result, Err(why) if why.kind() == io::ErrorKind::UnexpectedEof)
+}
+
+#[test]
+fn navigates_registers_and_fields() {
+    let paths = copy_configs("navigates_registers_and_fields", &["16bit_registers.json"]);
+    let (result, screen) = run(paths, keys(&[KeyCode::Right, KeyCode::Down, KeyCode::Enter, KeyCode::Down, KeyCode::Down]));
+    assert!(is_end_of_script(&result));
+    insta::assert_snapshot!(screen);
+}
+
+#[test]
+fn navigates_between_families() {
+    let paths = copy_configs("navigates_between_families", &["8bit_registers.json", "32bit_registers.json"]);
+    let (result, screen) = run(paths, keys(&[KeyCode::Esc, KeyCode::Right, KeyCode::Enter, KeyCode::Right]));
+    assert!(is_end_of_script(&result));
+    insta::assert_snapshot!(screen);
+}
+
+#[test]
+fn edits_field_info() {
+    let paths = copy_configs("edits_field_info", &["8bit_registers.json"]);
+    let mut script = vec![KeyCode::Down, KeyCode::Enter, KeyCode::Down, KeyCode::Enter];
+    script.extend(text("6"));
+    script.extend([KeyCode::Enter, KeyCode::Esc]);
+    let (result, screen) = run(paths, keys(&script));
+    assert!(is_end_of_script(&result));
+    insta::assert_snapshot!(screen);
+}
+
+#[test]
+fn saves_edits_on_quit() {
+    let paths = copy_configs("saves_edits_on_quit", &["8bit_registers.json"]);
+    let mut script = vec![KeyCode::Enter, KeyCode::Down, KeyCode::Enter];
+    script.extend(text("2"));
+    script.extend([KeyCode::Enter, KeyCode::Esc, KeyCode::Char('q')]);
+    let (result, _) = run(paths.clone(), keys(&script));
+    assert!(result.is_ok());
+
+    let saved = pull_existing_json(&paths[0]).unwrap();
+    assert_eq!(saved.registers[0].fields[0].msb, 2);
+    insta::assert_snapshot!(fs::read_to_string(&paths[0]).unwrap());
+}
+
+#[test]
+fn saves_only_modified_families() {
+    let paths = copy_configs("saves_only_modified_families", &["8bit_registers.json", "16bit_registers.json"]);
+    // Reformat the untouched family so any rewrite of it would be noticed
+    fs::write(&paths[1], fs::read_to_string(&paths[1]).unwrap().replace("\n", "\r\n")).unwrap();
+    let untouched = fs::read_to_string(&paths[1]).unwrap();
+
+    let mut script = vec![KeyCode::Enter, KeyCode::Enter];
+    script.extend(text("1"));
+    script.extend([KeyCode::Enter, KeyCode::Esc, KeyCode::Esc, KeyCode::Char('s')]);
+    let (result, screen) = run(paths.clone(), keys(&script));
+    assert!(is_end_of_script(&result));
+    assert_eq!(pull_existing_json(&paths[0]).unwrap().registers[0].fields[0].lsb, 1);
+    assert_eq!(fs::read_to_string(&paths[1]).unwrap(), untouched);
+    insta::assert_snapshot!(screen);
+}
+
+#[test]
+fn shows_generated_code_preview() {
+    let paths = copy_configs("shows_generated_code_preview", &["8bit_registers.json"]);
+    let (result, screen) = run(paths, keys(&[KeyCode::Right, KeyCode::Down, KeyCode::Char('p')]));
+    assert!(is_end_of_script(&result));
+    insta::assert_snapshot!(screen);
+}
+
+#[test]
+fn jumps_to_search_result() {
+    let paths = copy_configs("jumps_to_search_result", &["64bit_registers.json"]);
+    let mut script = vec![KeyCode::Char('/')];
+    script.extend(text("neg.high"));
+    script.push(KeyCode::Enter);
+    let (result, screen) = run(paths, keys(&script));
+    assert!(is_end_of_script(&result));
+    insta::assert_snapshot!(screen);
+}
+
+#[test]
+fn moving_the_last_register_leaves_an_empty_family() {
+    let paths = copy_configs("moving_the_last_register_leaves_an_empty_family", &["8bit_registers.json", "16bit_registers.json"]);
+    // Move both registers out, then try every navigation key on the empty family
+    let mut script = vec![KeyCode::Char('m'), KeyCode::Enter, KeyCode::Char('m'), KeyCode::Enter];
+    script.extend([KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down, KeyCode::Enter, KeyCode::Char('p')]);
+    let (result, screen) = run(paths.clone(), keys(&script));
+    assert!(is_end_of_script(&result));
+    insta::assert_snapshot!(screen);
+}
+
+// Screen positions in the 100x30 test terminal
+const REGISTER_TABS_ROW: u16 = 7;
+const FIELDS_COLUMN: u16 = 8;
+const FIELD_INFO_COLUMN: u16 = 30;
+const FIRST_FIELD_ROW: u16 = 14;
+
+#[test]
+fn clicking_a_register_tab_selects_it() {
+    let paths = copy_configs("clicking_a_register_tab_selects_it", &["16bit_registers.json"]);
+    let (result, screen) = run(paths, ScriptedEvents(VecDeque::from([click(20, REGISTER_TABS_ROW)])));
+    assert!(is_end_of_script(&result));
+    assert!(screen.contains("Register Name: NegHighLow_16"));
+}
+
+#[test]
+fn clicking_a_field_selects_it() {
+    let paths = copy_configs("clicking_a_field_selects_it", &["16bit_registers.json"]);
+    let (result, screen) = run(paths, ScriptedEvents(VecDeque::from([click(FIELDS_COLUMN, FIRST_FIELD_ROW + 1)])));
+    assert!(is_end_of_script(&result));
+    assert!(screen.contains("LSB: 8"));
+    // A single click on field info only selects it
+    let paths = copy_configs("clicking_a_field_selects_it", &["16bit_registers.json"]);
+    let (_, screen) = run(paths, ScriptedEvents(VecDeque::from([click(FIELD_INFO_COLUMN, FIRST_FIELD_ROW + 1)])));
+    assert!(screen.contains("LSB: 0") && !screen.contains("Popup"));
+}
+
+#[test]
+fn double_click_opens_the_edit_popup() {
+    let paths = copy_configs("double_click_opens_the_edit_popup", &["16bit_registers.json"]);
+    let script = [
+        click(FIELDS_COLUMN, FIRST_FIELD_ROW + 1),
+        click(FIELDS_COLUMN, FIRST_FIELD_ROW + 1),
+        click(FIELD_INFO_COLUMN, FIRST_FIELD_ROW + 1),
+        click(FIELD_INFO_COLUMN, FIRST_FIELD_ROW + 1),
+    ];
+    let (result, screen) = run(paths, ScriptedEvents(VecDeque::from(script)));
+    assert!(is_end_of_script(&result));
+    insta::assert_snapshot!(screen);
+}
+
+#[test]
+fn scroll_wheel_moves_through_the_list_under_it() {
+    let paths = copy_configs("scroll_wheel_moves_through_the_list_under_it", &["16bit_registers.json"]);
+    let script = [
+        mouse(MouseEventKind::ScrollDown, 20, REGISTER_TABS_ROW),
+        mouse(MouseEventKind::ScrollDown, FIELDS_COLUMN, FIRST_FIELD_ROW),
+    ];
+    let (result, screen) = run(paths, ScriptedEvents(VecDeque::from(script)));
+    assert!(is_end_of_script(&result));
+    assert!(screen.contains("Register Name: NegHighLow_16"));
+    assert!(screen.contains("LSB: 8"));
+
+    // Scrolling back up over the fields returns to the first one
+    let paths = copy_configs("scroll_wheel_moves_through_the_list_under_it", &["16bit_registers.json"]);
+    let script = [
+        mouse(MouseEventKind::ScrollDown, FIELDS_COLUMN, FIRST_FIELD_ROW),
+        mouse(MouseEventKind::ScrollUp, FIELDS_COLUMN, FIRST_FIELD_ROW),
+    ];
+    let (_, screen) = run(paths, ScriptedEvents(VecDeque::from(script)));
+    assert!(screen.contains("LSB: 0"));
+}
+
+#[test]
+fn failed_saves_are_reported_without_losing_edits() {
+    let paths = copy_configs("failed_saves_are_reported_without_losing_edits", &["8bit_registers.json", "16bit_registers.json"]);
+    let mut app = App::new(paths.clone(), TuiConfig::default()).unwrap();
+    for family in app.families.iter_mut() {
+        family.dirty = true;
+    }
+    // A directory in place of the file can't be opened for writing
+    fs::remove_file(&paths[0]).unwrap();
+    fs::create_dir(&paths[0]).unwrap();
+
+    app.save_family();
+    assert!(app.status.starts_with(&format!("Couldn't open {}", paths[0])), "{}", app.status);
+    assert!(!app.save_all());
+    assert!(app.families[0].dirty);
+    // The other family is still saved
+    assert!(!app.families[1].dirty);
+}
+
+#[test]
+fn saving_an_invalid_family_is_refused() {
+    let paths = copy_configs("saving_an_invalid_family_is_refused", &["8bit_registers.json"]);
+    let before = fs::read_to_string(&paths[0]).unwrap();
+    // Set the first field's lsb above its msb
+    let mut script = vec![KeyCode::Enter, KeyCode::Enter, KeyCode::Enter];
+    script.extend(text("7"));
+    script.extend([KeyCode::Enter, KeyCode::Esc, KeyCode::Char('s')]);
+    let (result, screen) = run(paths.clone(), keys(&script));
+    assert!(is_end_of_script(&result));
+    // The edit is kept, still marked unsaved
+    assert!(screen.contains("Refusing to write") && screen.contains("8Bit*") && screen.contains("LSB: 7"), "{}", screen);
+    assert_eq!(fs::read_to_string(&paths[0]).unwrap(), before);
+}
+
+#[test]
+fn unsaveable_edits_can_be_discarded_on_quit() {
+    let paths = copy_configs("unsaveable_edits_can_be_discarded_on_quit", &["8bit_registers.json"]);
+    let before = fs::read_to_string(&paths[0]).unwrap();
+    let mut script = vec![KeyCode::Enter, KeyCode::Enter, KeyCode::Enter];
+    script.extend(text("7"));
+    script.extend([KeyCode::Enter, KeyCode::Esc, KeyCode::Char('q')]);
+    // Quitting is refused while the edit can't be saved
+    let (result, screen) = run(paths.clone(), keys(&script));
+    assert!(is_end_of_script(&result));
+    assert!(screen.contains("Press Q to quit without saving."), "{}", screen);
+
+    script.push(KeyCode::Char('Q'));
+    let (result, _) = run(paths.clone(), keys(&script));
+    assert!(result.is_ok());
+    assert_eq!(fs::read_to_string(&paths[0]).unwrap(), before);
+}
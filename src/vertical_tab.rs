@@ -63,52 +63,113 @@ impl<'a> VerticalTabs<'a> {
     // }
 }
 
-impl<'a> Widget for VerticalTabs<'a> {
-    fn render(mut self, area: Rect, buf: &mut Buffer) {
+// Scroll position of a VerticalTabs widget, kept between frames so the list doesn't jump around
+#[derive(Debug, Clone, Default)]
+pub struct VerticalTabsState {
+    /// Index of the first visible tab
+    pub offset: usize,
+    /// Where the tabs were last drawn, used to map mouse clicks back to a tab
+    pub area: Rect,
+    /// How many tabs there were when last drawn
+    pub length: usize,
+}
+
+impl VerticalTabsState {
+    // Index of the tab drawn at the given screen position, if any
+    pub fn index_at(&self, column: u16, row: u16) -> Option<usize> {
+        let inside = column >= self.area.left() && column < self.area.right() && row >= self.area.top() && row < self.area.bottom();
+        if !inside {
+            return None;
+        }
+        let index = self.offset + (row - self.area.top()) as usize;
+        if index < self.length { Some(index) } else { None }
+    }
+
+    // Moves the offset as little as possible so that the selected tab is within the visible rows
+    fn scroll_to(&mut self, selected: usize, visible_rows: usize, titles_length: usize) {
+        if visible_rows == 0 {
+            return;
+        }
+        if selected < self.offset {
+            self.offset = selected;
+        } else if selected >= self.offset + visible_rows {
+            self.offset = selected + 1 - visible_rows;
+        }
+        // Don't leave empty rows at the bottom if the list shrank
+        self.offset = self.offset.min(titles_length.saturating_sub(visible_rows));
+    }
+}
+
+impl<'a> StatefulWidget for VerticalTabs<'a> {
+    type State = VerticalTabsState;
+
+    fn render(mut self, area: Rect, buf: &mut Buffer, state: &mut VerticalTabsState) {
         buf.set_style(area, self.style);
+        let has_block = self.block.is_some();
         let tabs_area = match self.block.take() {
             Some(b) => {
-                let mut inner_area = b.inner(area);
+                let inner_area = b.inner(area);
                 b.render(area, buf);
-                // I do this to align it better with the box
-                inner_area.y -= 1;
                 inner_area
             }
             None => area,
         };
 
+        state.area = tabs_area;
+        state.length = self.titles.len();
         if tabs_area.height < 1 {
             return;
         }
 
-        let mut y = tabs_area.top();
+        let visible_rows = tabs_area.height as usize;
         let titles_length = self.titles.len();
-        for (i, title) in self.titles.into_iter().enumerate() {
-            let last_title = titles_length - 1 == i;
-            y = y.saturating_add(1);
-            let remaining_height = tabs_area.bottom().saturating_sub(y);
-            if remaining_height == 0 {
-                break;
-            }
-            let pos = buf.set_spans(tabs_area.left(), y, &title, remaining_height);
+        state.scroll_to(self.selected, visible_rows, titles_length);
+
+        for (row, (i, title)) in self.titles.into_iter().enumerate().skip(state.offset).take(visible_rows).enumerate() {
+            let y = tabs_area.top() + row as u16;
+            buf.set_spans(tabs_area.left(), y, &title, tabs_area.width);
             if i == self.selected {
                 buf.set_style(
                     Rect {
                         x: tabs_area.left(),
                         y,
-                        width: title.width() as u16,
+                        width: (title.width() as u16).min(tabs_area.width),
                         height: 1,
                     },
                     self.highlight_style,
                 );
             }
-            y = pos.1.saturating_add(1);
-            let remaining_height = tabs_area.bottom().saturating_sub(y);
-            if remaining_height == 0 || last_title {
-                break;
-            }
-            // let pos = buf.set_span(tabs_area.left(), y, &self.divider, remaining_height);
-            y = pos.1;
         }
+
+        // Scroll indicators go on the border when there is one, otherwise over the first/last row
+        let hidden_above = state.offset;
+        let hidden_below = titles_length.saturating_sub(state.offset + visible_rows);
+        let (top, bottom) = if has_block {
+            (area.top(), area.bottom().saturating_sub(1))
+        } else {
+            (tabs_area.top(), tabs_area.bottom().saturating_sub(1))
+        };
+        if hidden_above > 0 {
+            draw_indicator(buf, area, top, format!("▲ {}", hidden_above));
+        }
+        if hidden_below > 0 {
+            draw_indicator(buf, area, bottom, format!("▼ {}", hidden_below));
+        }
+    }
+}
+
+impl<'a> Widget for VerticalTabs<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut state = VerticalTabsState::default();
+        StatefulWidget::render(self, area, buf, &mut state);
+    }
+}
+
+fn draw_indicator(buf: &mut Buffer, area: Rect, y: u16, text: String) {
+    let width = text.chars().count() as u16;
+    if area.width < width + 2 {
+        return;
     }
+    let x = area.right() - width - 1;
+    buf.set_string(x, y, text, Style::default().add_modifier(Modifier::BOLD));
 }
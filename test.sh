@@ -6,8 +6,10 @@ PROJECT_BINARY=${PROJECT_DIR}target/debug/register_generator
 TEST_DIR=${PROJECT_DIR}test/
 BUILD_DIR=${TEST_DIR}build/
 TEST_BINARY=${BUILD_DIR}src/test
+MMIO_TEST_BINARY=${BUILD_DIR}src/mmio_test
 CFG_DIR=${TEST_DIR}cfg/
 ENV_DIR=${TEST_DIR}environment/
+MMIO_ENV_DIR=${TEST_DIR}environment_mmio/
 
 # Colours
 RED="\e[31m"
@@ -25,20 +27,41 @@ then
     exit 1
 fi
 
-mkdir ${ENV_DIR}
-cp ${PROJECT_BINARY} ${ENV_DIR}
-cd ${ENV_DIR}
+cargo test
+RESULT=$?
+if [ ${RESULT} -ne 0 ]
+then
+    echo -e "${BOLDRED}CARGO TEST FAIL${ENDCOLOR}"
+    exit 1
+fi
+
+${PROJECT_BINARY} fmt --check --path ${CFG_DIR}
+RESULT=$?
+if [ ${RESULT} -ne 0 ]
+then
+    echo -e "${BOLDRED}CONFIG FORMAT FAIL${ENDCOLOR}"
+    exit 1
+fi
 
-# Generate our headers
-for filename in ${CFG_DIR}*; do
-    ./register_generator generate --path $filename
-    RESULT=$?
-    if [ ${RESULT} -ne 0 ]
-    then
-        echo -e "${BOLDRED}HEADER GENERATION FAIL${ENDCOLOR}"
-        exit 1
-    fi
-done
+# Generate our headers, once plain and once with --mmio, since both share the base register files
+generate_headers() {
+    mkdir $1
+    cp ${PROJECT_BINARY} $1
+    cd $1
+
+    for filename in ${CFG_DIR}*; do
+        ./register_generator generate --path $filename $2
+        RESULT=$?
+        if [ ${RESULT} -ne 0 ]
+        then
+            echo -e "${BOLDRED}HEADER GENERATION FAIL${ENDCOLOR}"
+            exit 1
+        fi
+    done
+}
+
+generate_headers ${ENV_DIR}
+generate_headers ${MMIO_ENV_DIR} --mmio
 
 # Create the build directory if it doesn't exist
 if [ ! -d ${BUILD_DIR} ]
@@ -57,7 +80,7 @@ then
     exit 1
 fi
 
-# Build the test binary
+# Build the test binaries
 make
 RESULT=$?
 if [ ${RESULT} -ne 0 ]
@@ -66,16 +89,24 @@ then
     exit 1
 fi
 
+# Run the test binaries
 cd ${ENV_DIR}
-
-# Run the test binary
 ${TEST_BINARY}
 RESULT=$?
+if [ ${RESULT} -ne 0 ]
+then
+    echo -e "${BOLDRED}TEST FAIL${ENDCOLOR}"
+    exit 1
+fi
+
+cd ${MMIO_ENV_DIR}
+${MMIO_TEST_BINARY}
+RESULT=$?
 if [ ${RESULT} -eq 0 ]
 then
     echo -e "${BOLDGREEN}SUCCESS${ENDCOLOR}"
     exit 0
 else
-    echo -e "${BOLDRED}TEST FAIL${ENDCOLOR}"
+    echo -e "${BOLDRED}MMIO TEST FAIL${ENDCOLOR}"
     exit 1
 fi
@@ -0,0 +1,57 @@
+#include <cstdlib>
+#include <gtest/gtest.h>
+
+#include <MmioRegisters.h>
+
+// Stands in for the peripheral's address space
+struct FakeMmio {
+    alignas(4) uint8_t bytes[8] = {};
+
+    uintptr_t base() { return reinterpret_cast<uintptr_t>(bytes); }
+    volatile uint32_t *control() { return reinterpret_cast<volatile uint32_t *>(bytes); }
+    volatile uint8_t *status() { return reinterpret_cast<volatile uint8_t *>(bytes + 4); }
+};
+
+TEST(TestMmio, ReadWriteTest) {
+    FakeMmio mmio;
+    Control_MMIO reg;
+
+    *mmio.control() = 0xFD01;
+    reg.read_from(mmio.control());
+    EXPECT_EQ(reg.get_enable(), 1);
+    EXPECT_EQ(reg.get_trim(), -3);
+
+    EXPECT_EQ(reg.set_enable(0), true);
+    reg.write_to(mmio.control());
+    EXPECT_EQ(*mmio.control(), 0xFD00);
+}
+
+TEST(TestMmio, ModifyTest) {
+    FakeMmio mmio;
+    Control_MMIO reg;
+
+    *mmio.control() = 0xFFFF'0001;
+    reg.modify(mmio.control(), [](Control_MMIO &current) { current.set_trim(5); });
+    EXPECT_EQ(*mmio.control(), 0xFFFF'0501);
+    EXPECT_EQ(reg.get_trim(), 5);
+}
+
+TEST(TestMmio, PeripheralTest) {
+    FakeMmio mmio;
+    MmioPeripheral peripheral(mmio.base());
+
+    EXPECT_EQ(MmioPeripheral::base_address, 0x4000'0000);
+    EXPECT_EQ(peripheral.Control_MMIO.get_address(), mmio.control());
+    EXPECT_EQ(peripheral.Status_MMIO.get_address(), mmio.status());
+
+    *mmio.status() = 0x01;
+    EXPECT_EQ(peripheral.Status_MMIO.read().get_ready(), 1);
+
+    peripheral.Control_MMIO.modify([](Control_MMIO &reg) { reg.set_enable(1); reg.set_trim(-1); });
+    EXPECT_EQ(*mmio.control(), 0xFF01);
+    EXPECT_EQ(*mmio.status(), 0x01);
+
+    Control_MMIO reg;
+    peripheral.Control_MMIO.write(reg);
+    EXPECT_EQ(*mmio.control(), 0x0);
+}